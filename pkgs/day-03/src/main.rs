@@ -1,5 +1,3 @@
-use std::collections::HashSet;
-
 use nom::{
     character::{complete::alpha1, complete::line_ending},
     combinator::{all_consuming, map},
@@ -9,8 +7,20 @@ use nom::{
 
 fn main() {
     let (_, rucksacks) = parse(include_str!("in").trim()).unwrap();
-    dbg!(part1(&rucksacks));
-    dbg!(part2(&rucksacks));
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["groups" | "windows", size] if size.parse::<usize>().ok() == Some(0) => {
+            eprintln!("size must be at least 1");
+            std::process::exit(1);
+        }
+        ["groups", size] => print_common(&common_in_groups(&rucksacks, size.parse().unwrap())),
+        ["windows", size] => print_common(&common_in_windows(&rucksacks, size.parse().unwrap())),
+        ["explain"] => explain(&rucksacks),
+        _ => {
+            dbg!(part1(&rucksacks));
+            dbg!(part2(&rucksacks));
+        }
+    }
 }
 
 fn parse(input: &str) -> IResult<&str, Vec<Vec<char>>> {
@@ -27,29 +37,164 @@ fn get_cost(letter: char) -> u64 {
         + if letter.is_ascii_uppercase() { 27 } else { 1 }
 }
 
-fn part1(rucksacks: &[Vec<char>]) -> u64 {
-    let mut sum = 0;
-    for rucksack in rucksacks {
+/// Set of item types packed into a 52-bit mask, bit `n` is the item with priority `n + 1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Items(u64);
+
+impl Items {
+    const ALL: Items = Items((1 << 52) - 1);
+
+    fn from_letters(letters: &[char]) -> Self {
+        Items(
+            letters
+                .iter()
+                .fold(0, |mask, &letter| mask | 1 << (get_cost(letter) - 1)),
+        )
+    }
+
+    fn intersection(self, other: Self) -> Self {
+        Items(self.0 & other.0)
+    }
+
+    fn contains(self, letter: char) -> bool {
+        self.0 & 1 << (get_cost(letter) - 1) != 0
+    }
+
+    fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    fn letters(self) -> impl Iterator<Item = char> {
+        (0..52u8)
+            .filter(move |bit| self.0 & 1 << bit != 0)
+            .map(|bit| match bit {
+                0..=25 => (b'a' + bit) as char,
+                _ => (b'A' + bit - 26) as char,
+            })
+    }
+
+    fn cost(self) -> u64 {
+        self.letters().map(get_cost).sum()
+    }
+}
+
+fn common<'a>(rucksacks: impl IntoIterator<Item = &'a [char]>) -> Items {
+    rucksacks
+        .into_iter()
+        .map(Items::from_letters)
+        .fold(Items::ALL, Items::intersection)
+}
+
+fn common_in_compartments(rucksack: &[char]) -> Items {
+    let (left, right) = rucksack.split_at(rucksack.len() / 2);
+    common([left, right])
+}
+
+fn common_in_groups(rucksacks: &[Vec<char>], size: usize) -> Vec<Items> {
+    rucksacks
+        .chunks(size)
+        .map(|group| common(group.iter().map(Vec::as_slice)))
+        .collect()
+}
+
+fn common_in_windows(rucksacks: &[Vec<char>], size: usize) -> Vec<Items> {
+    rucksacks
+        .windows(size)
+        .map(|window| common(window.iter().map(Vec::as_slice)))
+        .collect()
+}
+
+fn print_common(common: &[Items]) {
+    for (i, items) in common.iter().enumerate() {
+        println!(
+            "{i}: {} ({})",
+            items.letters().collect::<String>(),
+            items.cost()
+        );
+    }
+}
+
+fn highlight(letters: &[char], shared: Items) -> String {
+    letters
+        .iter()
+        .map(|&letter| {
+            if shared.contains(letter) {
+                format!("[{letter}]")
+            } else {
+                letter.to_string()
+            }
+        })
+        .collect()
+}
+
+fn describe(shared: Items) -> String {
+    match shared.len() {
+        0 => "nothing shared".to_owned(),
+        _ => shared
+            .letters()
+            .map(|letter| format!("{letter} = {}", get_cost(letter)))
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+fn explain(rucksacks: &[Vec<char>]) {
+    let mut malformed = Vec::new();
+
+    println!("rucksacks:");
+    for (i, rucksack) in rucksacks.iter().enumerate() {
         let (left, right) = rucksack.split_at(rucksack.len() / 2);
-        let left = left.iter().copied().collect::<HashSet<char>>();
-        let right = right.iter().copied().collect::<HashSet<char>>();
-        let common = left.intersection(&right).collect::<Vec<_>>();
-        assert_eq!(common.len(), 1);
-        sum += get_cost(*common[0]);
+        let shared = common_in_compartments(rucksack);
+        println!(
+            "{i:>4}: {} | {} -> {}",
+            highlight(left, shared),
+            highlight(right, shared),
+            describe(shared)
+        );
+        if rucksack.len() % 2 == 1 {
+            malformed.push(format!("rucksack {i} has odd length {}", rucksack.len()));
+        }
+        if shared.len() != 1 {
+            malformed.push(format!(
+                "rucksack {i} shares {} items instead of 1",
+                shared.len()
+            ));
+        }
+    }
+
+    println!("groups:");
+    for (i, badge) in common_in_groups(rucksacks, 3).into_iter().enumerate() {
+        println!("{i:>4}: badge {}", describe(badge));
+        if badge.len() != 1 {
+            malformed.push(format!("group {i} has {} badges instead of 1", badge.len()));
+        }
+    }
+    if !rucksacks.len().is_multiple_of(3) {
+        malformed.push(format!(
+            "last group has only {} rucksacks",
+            rucksacks.len() % 3
+        ));
     }
-    sum
+
+    println!("malformed:");
+    for problem in &malformed {
+        println!("  {problem}");
+    }
+    if malformed.is_empty() {
+        println!("  none");
+    }
+}
+
+fn part1(rucksacks: &[Vec<char>]) -> u64 {
+    rucksacks
+        .iter()
+        .map(|rucksack| common_in_compartments(rucksack).cost())
+        .sum()
 }
 
 fn part2(rucksacks: &[Vec<char>]) -> u64 {
-    let mut sum = 0;
-    for group in rucksacks.chunks(3) {
-        let common = group
-            .iter()
-            .map(|rucksack| rucksack.iter().copied().collect::<HashSet<char>>())
-            .reduce(|x, y| x.intersection(&y).copied().collect::<HashSet<_>>())
-            .unwrap();
-        assert_eq!(common.len(), 1);
-        sum += get_cost(*common.iter().next().unwrap());
-    }
-    sum
+    common_in_groups(rucksacks, 3)
+        .into_iter()
+        .map(Items::cost)
+        .sum()
 }
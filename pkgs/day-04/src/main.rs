@@ -1,5 +1,4 @@
-use std::ops::RangeInclusive;
-
+use intervals::IntervalSet;
 use nom::{
     bytes::complete::tag,
     character::complete::{digit1, line_ending},
@@ -11,46 +10,186 @@ use nom::{
 
 fn main() {
     let (_, sections) = parse(include_str!("in").trim()).unwrap();
-    dbg!(part1(&sections));
-    dbg!(part2(&sections));
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["roster"] => print!("{}", analyze_roster(&sections).to_text()),
+        ["roster", "dot"] => print!("{}", analyze_roster(&sections).to_dot()),
+        ["uncovered"] => print_sections(&uncovered(&sections)),
+        ["covered", k] => print_sections(&covered_by_more_than(&sections, k.parse().unwrap())),
+        _ => {
+            dbg!(part1(&sections));
+            dbg!(part2(&sections));
+        }
+    }
 }
 
-fn parse_sections(input: &str) -> IResult<&str, RangeInclusive<u64>> {
+type Pair = (IntervalSet, IntervalSet);
+
+fn parse_sections(input: &str) -> IResult<&str, IntervalSet> {
     map_opt(
         separated_pair(digit1, tag("-"), digit1),
         |(left, right): (&str, &str)| {
-            let left = left.parse::<u64>().ok()?;
-            let right = right.parse::<u64>().ok()?;
-            Some(left..=right)
+            let left = left.parse::<i64>().ok()?;
+            let right = right.parse::<i64>().ok()?;
+            Some(IntervalSet::from(left..=right))
         },
     )(input)
 }
 
-fn parse(input: &str) -> IResult<&str, Vec<(RangeInclusive<u64>, RangeInclusive<u64>)>> {
+fn parse(input: &str) -> IResult<&str, Vec<Pair>> {
     all_consuming(separated_list1(
         line_ending,
         separated_pair(parse_sections, tag(","), parse_sections),
     ))(input)
 }
 
-fn part1(sections: &[(RangeInclusive<u64>, RangeInclusive<u64>)]) -> usize {
-    sections
+fn elves(sections: &[Pair]) -> impl Iterator<Item = &IntervalSet> {
+    sections.iter().flat_map(|(left, right)| [left, right])
+}
+
+fn uncovered(sections: &[Pair]) -> IntervalSet {
+    let covered = IntervalSet::covered_at_least(elves(sections), 1);
+    covered.hull().map_or_else(IntervalSet::new, |hull| {
+        IntervalSet::from(hull).difference(&covered)
+    })
+}
+
+fn covered_by_more_than(sections: &[Pair], k: usize) -> IntervalSet {
+    IntervalSet::covered_at_least(elves(sections), k + 1)
+}
+
+fn print_sections(set: &IntervalSet) {
+    for range in set.ranges() {
+        println!("{}-{}", range.start(), range.end());
+    }
+    println!("{} sections", set.len());
+}
+
+struct Roster<'a> {
+    elves: Vec<(String, &'a IntervalSet)>,
+    conflicts: Vec<(usize, usize)>,
+    max_depth: usize,
+    busiest: IntervalSet,
+    droppable: Vec<usize>,
+}
+
+fn analyze_roster(sections: &[Pair]) -> Roster<'_> {
+    let elves = sections
         .iter()
-        .filter(|(left, right)| {
-            left.contains(right.start()) && left.contains(right.end())
-                || right.contains(left.start()) && right.contains(left.end())
+        .enumerate()
+        .flat_map(|(i, (left, right))| {
+            [
+                (format!("{}a", i + 1), left),
+                (format!("{}b", i + 1), right),
+            ]
         })
+        .collect::<Vec<_>>();
+
+    let mut by_start = (0..elves.len())
+        .filter(|&i| !elves[i].1.is_empty())
+        .collect::<Vec<_>>();
+    by_start.sort_by_key(|&i| elves[i].1.hull().map(|hull| *hull.start()));
+    let mut conflicts = Vec::new();
+    for (n, &i) in by_start.iter().enumerate() {
+        let end = *elves[i].1.hull().unwrap().end();
+        for &j in &by_start[n + 1..] {
+            if *elves[j].1.hull().unwrap().start() > end {
+                break;
+            }
+            if elves[i].1.overlaps(elves[j].1) {
+                conflicts.push((i.min(j), i.max(j)));
+            }
+        }
+    }
+    conflicts.sort_unstable();
+
+    let max_depth = IntervalSet::max_depth(elves.iter().map(|(_, sections)| *sections));
+    // With no sections assigned at all nothing is busy, not every section at depth 0
+    let busiest = match max_depth {
+        0 => IntervalSet::new(),
+        _ => IntervalSet::covered_at_least(elves.iter().map(|(_, sections)| *sections), max_depth),
+    };
+
+    let mut kept = vec![true; elves.len()];
+    let mut smallest_first = (0..elves.len()).collect::<Vec<_>>();
+    smallest_first.sort_by_key(|&i| elves[i].1.len());
+    let mut droppable = Vec::new();
+    for i in smallest_first {
+        let doubled = IntervalSet::covered_at_least(
+            elves
+                .iter()
+                .zip(&kept)
+                .filter(|(_, &kept)| kept)
+                .map(|((_, sections), _)| *sections),
+            2,
+        );
+        if doubled.is_superset(elves[i].1) {
+            kept[i] = false;
+            droppable.push(i);
+        }
+    }
+    droppable.sort_unstable();
+
+    Roster {
+        elves,
+        conflicts,
+        max_depth,
+        busiest,
+        droppable,
+    }
+}
+
+impl Roster<'_> {
+    fn to_text(&self) -> String {
+        let mut text = format!("conflicts: {} pairs\n", self.conflicts.len());
+        for &(i, j) in &self.conflicts {
+            let (left, right) = (&self.elves[i], &self.elves[j]);
+            text += &format!("  {} ({}) -- {} ({})\n", left.0, left.1, right.0, right.1);
+        }
+        text += &format!(
+            "max depth: {} elves on sections {}\n",
+            self.max_depth, self.busiest
+        );
+        text += &format!("droppable: {} assignments\n", self.droppable.len());
+        for &i in &self.droppable {
+            let (label, sections) = &self.elves[i];
+            text += &format!("  {label} ({sections}) is covered by the rest\n");
+        }
+        text
+    }
+
+    fn to_dot(&self) -> String {
+        let mut dot = String::from("graph roster {\n");
+        dot += &format!(
+            "  label=\"max depth {} on sections {}\";\n",
+            self.max_depth, self.busiest
+        );
+        for (i, (label, sections)) in self.elves.iter().enumerate() {
+            let style = if self.droppable.contains(&i) {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            dot += &format!("  \"{label}\" [label=\"{label}\\n{sections}\"{style}];\n");
+        }
+        for &(i, j) in &self.conflicts {
+            dot += &format!("  \"{}\" -- \"{}\";\n", self.elves[i].0, self.elves[j].0);
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+fn part1(sections: &[Pair]) -> usize {
+    sections
+        .iter()
+        .filter(|(left, right)| left.is_superset(right) || right.is_superset(left))
         .count()
 }
 
-fn part2(sections: &[(RangeInclusive<u64>, RangeInclusive<u64>)]) -> usize {
+fn part2(sections: &[Pair]) -> usize {
     sections
         .iter()
-        .filter(|(left, right)| {
-            left.contains(right.start())
-                || left.contains(right.end())
-                || right.contains(left.start())
-                || right.contains(left.end())
-        })
+        .filter(|(left, right)| left.overlaps(right))
         .count()
 }
@@ -6,14 +6,71 @@ use nom::{
     character::complete::{alpha1, char, digit1, line_ending},
     combinator::{all_consuming, map, map_res, value},
     multi::separated_list1,
-    sequence::{delimited, tuple},
+    sequence::{delimited, preceded, tuple},
     IResult,
 };
 
 fn main() {
     let (_, (layers, steps)) = parse(include_str!("in").trim_end()).unwrap();
-    dbg!(part1(&layers, &steps));
-    dbg!(part2(&layers, &steps));
+    let steps = index_stacks(&steps).unwrap_or_else(|invalid| {
+        println!("{invalid}");
+        std::process::exit(1);
+    });
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["crane", ref model @ ..] => {
+            let crane = crane(model).expect("unknown crane model");
+            dbg!(operate(crane.as_ref(), &layers, &steps));
+        }
+        ["render"] => print!("{}", render(&fill_pallets(&layers))),
+        ["animate", every, ref model @ ..] => {
+            let crane = crane(model).expect("unknown crane model");
+            animate(crane.as_ref(), &layers, &steps, every.parse().unwrap());
+        }
+        ["validate"] => match validate(&fill_pallets(&layers), &steps) {
+            Ok(()) => println!("all {} steps are valid", steps.len()),
+            Err(invalid) => println!("{invalid}"),
+        },
+        ["rewind", end, ref model @ ..] => {
+            let crane = crane(model).expect("unknown crane model");
+            let mut pallets = read_drawing(end);
+            match rewind(crane.as_ref(), &mut pallets, &steps) {
+                Ok(()) => print!("{}", render(&pallets)),
+                Err(invalid) => println!("{invalid}"),
+            }
+        }
+        ["undo", n, ref model @ ..] => {
+            let crane = crane(model).expect("unknown crane model");
+            let mut pallets = fill_pallets(&layers);
+            let mut log = UndoLog::default();
+            for &step in &steps {
+                if let Err(invalid) = log.apply(crane.as_ref(), &mut pallets, step) {
+                    println!("{invalid}");
+                    break;
+                }
+            }
+            for _ in 0..n.parse().unwrap() {
+                if let Some((count, from, to)) = log.undo(crane.as_ref(), &mut pallets) {
+                    println!("undo move {count} from {} to {}", from + 1, to + 1);
+                }
+            }
+            print!("{}", render(&pallets));
+        }
+        ["plan", target, ref model @ ..] => {
+            let crane = crane(model).expect("unknown crane model");
+            let start = fill_pallets(&layers);
+            let plan = plan(crane.as_ref(), &start, &read_drawing(target))
+                .expect("target is unreachable from the start drawing");
+            println!("{}", render(&start));
+            for (count, from, to) in plan {
+                println!("move {count} from {} to {}", from + 1, to + 1);
+            }
+        }
+        _ => {
+            dbg!(part1(&layers, &steps));
+            dbg!(part2(&layers, &steps));
+        }
+    }
 }
 
 fn empty_slot(input: &str) -> IResult<&str, &str> {
@@ -27,7 +84,7 @@ fn a_crate(input: &str) -> IResult<&str, &str> {
 fn slot(input: &str) -> IResult<&str, Slot> {
     map(alt((empty_slot, a_crate)), |s: &str| match s {
         "   " => None,
-        cargo => Some(cargo.chars().nth(0).unwrap()),
+        cargo => Some(cargo.chars().next().unwrap()),
     })(input)
 }
 
@@ -53,6 +110,8 @@ fn a_number(input: &str) -> IResult<&str, usize> {
     map_res(digit1, |s: &str| s.parse::<usize>())(input)
 }
 
+/// Crate count, source and destination stack. Stacks are numbered from 1 as written
+/// until `index_stacks` turns them into indices
 type Step = (usize, usize, usize);
 
 fn step(input: &str) -> IResult<&str, Step> {
@@ -65,7 +124,7 @@ fn step(input: &str) -> IResult<&str, Step> {
             tag(" to "),
             a_number,
         )),
-        |(_, count, _, from, _, to)| (count, from - 1, to - 1),
+        |(_, count, _, from, _, to)| (count, from, to),
     )(input)
 }
 
@@ -73,9 +132,22 @@ fn steps(input: &str) -> IResult<&str, Vec<Step>> {
     separated_list1(line_ending, step)(input)
 }
 
-fn parse(input: &str) -> IResult<&str, (Vec<Slots>, Vec<Step>)> {
+fn drawing(input: &str) -> IResult<&str, Vec<Slots>> {
     let (input, layers) = layers(input)?;
-    let (input, _) = delimited(line_ending, numbers, line_ending)(input)?;
+    let (input, _) = preceded(line_ending, numbers)(input)?;
+    Ok((input, layers))
+}
+
+/// Stacks from a file holding just a drawing, without the procedure
+fn read_drawing(path: &str) -> Vec<VecDeque<char>> {
+    let input = std::fs::read_to_string(path).unwrap();
+    let (_, layers) = all_consuming(drawing)(input.trim_end_matches(['\r', '\n'])).unwrap();
+    fill_pallets(&layers)
+}
+
+fn parse(input: &str) -> IResult<&str, (Vec<Slots>, Vec<Step>)> {
+    let (input, layers) = drawing(input)?;
+    let (input, _) = line_ending(input)?;
     let (input, _) = line_ending(input)?;
     let (input, steps) = all_consuming(steps)(input)?;
     Ok((input, (layers, steps)))
@@ -96,34 +168,442 @@ fn fill_pallets(layers: &[Slots]) -> Vec<VecDeque<char>> {
     pallets
 }
 
-fn part1(layers: &[Slots], steps: &[Step]) -> String {
+/// Draws stacks back in the puzzle format, top crates first, with the numbered base line
+fn render(pallets: &[VecDeque<char>]) -> String {
+    let height = pallets.iter().map(VecDeque::len).max().unwrap_or(0);
+    let mut drawing = String::new();
+    for level in (1..=height).rev() {
+        let layer = pallets
+            .iter()
+            .map(|pallet| match pallet.len().checked_sub(level) {
+                Some(i) => format!("[{}]", pallet[i]),
+                None => "   ".to_owned(),
+            })
+            .collect::<Vec<_>>();
+        drawing += &layer.join(" ");
+        drawing.push('\n');
+    }
+    let numbers = (1..=pallets.len())
+        .map(|i| format!(" {i} "))
+        .collect::<Vec<_>>();
+    drawing += &numbers.join(" ");
+    drawing.push('\n');
+    drawing
+}
+
+/// Prints the drawing after every `every`-th step and after the last one
+fn animate(crane: &dyn Crane, layers: &[Slots], steps: &[Step], every: usize) {
     let mut pallets = fill_pallets(layers);
-    for &(count, from, to) in steps {
+    print!("{}", render(&pallets));
+    for (i, &(count, from, to)) in steps.iter().enumerate() {
+        crane.lift(&mut pallets, (count, from, to));
+        let n = i + 1;
+        if n % every.max(1) == 0 || n == steps.len() {
+            println!("\n[{n}] move {count} from {} to {}", from + 1, to + 1);
+            print!("{}", render(&pallets));
+        }
+    }
+}
+
+trait Crane {
+    fn lift(&self, pallets: &mut [VecDeque<char>], step: Step);
+    /// Reverts a `lift` of the same step
+    fn undo(&self, pallets: &mut [VecDeque<char>], step: Step);
+}
+
+/// Moves crates one at a time
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn lift(&self, pallets: &mut [VecDeque<char>], (count, from, to): Step) {
         for _ in 0..count {
             let cargo = pallets[from].pop_front().unwrap();
             pallets[to].push_front(cargo);
         }
     }
-    pallets
+
+    fn undo(&self, pallets: &mut [VecDeque<char>], (count, from, to): Step) {
+        self.lift(pallets, (count, to, from));
+    }
+}
+
+/// Moves the whole batch at once, keeping its order
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn lift(&self, pallets: &mut [VecDeque<char>], (count, from, to): Step) {
+        let stash = pallets[from].drain(..count).collect::<Vec<_>>();
+        for cargo in stash.into_iter().rev() {
+            pallets[to].push_front(cargo);
+        }
+    }
+
+    fn undo(&self, pallets: &mut [VecDeque<char>], (count, from, to): Step) {
+        self.lift(pallets, (count, to, from));
+    }
+}
+
+/// Moves batches like 9001, but splits moves larger than `capacity`
+struct Capped {
+    capacity: usize,
+}
+
+impl Crane for Capped {
+    fn lift(&self, pallets: &mut [VecDeque<char>], (count, from, to): Step) {
+        let mut left = count;
+        while left > 0 {
+            let batch = left.min(self.capacity);
+            CrateMover9001.lift(pallets, (batch, from, to));
+            left -= batch;
+        }
+    }
+
+    fn undo(&self, pallets: &mut [VecDeque<char>], (count, from, to): Step) {
+        let last = match count % self.capacity {
+            0 => self.capacity.min(count),
+            rest => rest,
+        };
+        let mut left = count;
+        let mut batch = last;
+        while left > 0 {
+            CrateMover9001.lift(pallets, (batch, to, from));
+            left -= batch;
+            batch = self.capacity;
+        }
+    }
+}
+
+fn crane(model: &[&str]) -> Option<Box<dyn Crane>> {
+    match model {
+        [] | ["9000"] => Some(Box::new(CrateMover9000)),
+        ["9001"] => Some(Box::new(CrateMover9001)),
+        ["capped", capacity] => match capacity.parse() {
+            Ok(capacity) if capacity > 0 => Some(Box::new(Capped { capacity })),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum InvalidStep {
+    /// `stack` is numbered from 1, like in the procedure
+    NoSuchStack { index: usize, stack: usize },
+    NotEnoughCrates {
+        index: usize,
+        count: usize,
+        available: usize,
+    },
+}
+
+impl std::fmt::Display for InvalidStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoSuchStack { index, stack } => {
+                write!(f, "step {}: there is no stack {stack}", index + 1)
+            }
+            Self::NotEnoughCrates {
+                index,
+                count,
+                available,
+            } => write!(
+                f,
+                "step {}: moves {count} crates but the stack holds {available}",
+                index + 1
+            ),
+        }
+    }
+}
+
+/// Turns the stack numbers of the procedure into indices, there is no stack 0
+fn index_stacks(steps: &[Step]) -> Result<Vec<Step>, InvalidStep> {
+    steps
         .iter()
-        .map(|pallet| pallet.front().unwrap())
+        .enumerate()
+        .map(|(index, &(count, from, to))| {
+            let index_of = |stack: usize| {
+                stack
+                    .checked_sub(1)
+                    .ok_or(InvalidStep::NoSuchStack { index, stack })
+            };
+            Ok((count, index_of(from)?, index_of(to)?))
+        })
         .collect()
 }
 
-fn part2(layers: &[Slots], steps: &[Step]) -> String {
-    let mut pallets = fill_pallets(layers);
-    for &(count, from, to) in steps {
-        let mut stash = Vec::new();
-        for _ in 0..count {
-            stash.push(pallets[from].pop_front().unwrap());
+fn check(heights: &[usize], index: usize, (count, from, to): Step) -> Result<(), InvalidStep> {
+    for stack in [from, to] {
+        if stack >= heights.len() {
+            return Err(InvalidStep::NoSuchStack {
+                index,
+                stack: stack + 1,
+            });
         }
-        stash.reverse();
-        for cargo in stash {
-            pallets[to].push_front(cargo);
+    }
+    if count > heights[from] {
+        return Err(InvalidStep::NotEnoughCrates {
+            index,
+            count,
+            available: heights[from],
+        });
+    }
+    Ok(())
+}
+
+/// Finds the first step that cannot be carried out, every crane model moves the same amounts
+fn validate(pallets: &[VecDeque<char>], steps: &[Step]) -> Result<(), InvalidStep> {
+    let mut heights = pallets.iter().map(VecDeque::len).collect::<Vec<_>>();
+    for (index, &(count, from, to)) in steps.iter().enumerate() {
+        check(&heights, index, (count, from, to))?;
+        heights[from] -= count;
+        heights[to] += count;
+    }
+    Ok(())
+}
+
+/// Steps applied so far, so the procedure can be run backwards
+#[derive(Debug, Default)]
+struct UndoLog {
+    steps: Vec<Step>,
+}
+
+impl UndoLog {
+    fn apply(
+        &mut self,
+        crane: &dyn Crane,
+        pallets: &mut [VecDeque<char>],
+        step: Step,
+    ) -> Result<(), InvalidStep> {
+        let heights = pallets.iter().map(VecDeque::len).collect::<Vec<_>>();
+        check(&heights, self.steps.len(), step)?;
+        crane.lift(pallets, step);
+        self.steps.push(step);
+        Ok(())
+    }
+
+    fn undo(&mut self, crane: &dyn Crane, pallets: &mut [VecDeque<char>]) -> Option<Step> {
+        let step = self.steps.pop()?;
+        crane.undo(pallets, step);
+        Some(step)
+    }
+}
+
+/// Recovers the starting stacks from the final arrangement and the procedure,
+/// stopping at the last step whose crates are not on the stack it moved them to
+fn rewind(
+    crane: &dyn Crane,
+    pallets: &mut [VecDeque<char>],
+    steps: &[Step],
+) -> Result<(), InvalidStep> {
+    for (index, &(count, from, to)) in steps.iter().enumerate().rev() {
+        let heights = pallets.iter().map(VecDeque::len).collect::<Vec<_>>();
+        check(&heights, index, (count, to, from))?;
+        crane.undo(pallets, (count, from, to));
+    }
+    Ok(())
+}
+
+/// Number of crates at the bottom of the stack that are already where the target wants them
+fn settled(pallet: &VecDeque<char>, target: &VecDeque<char>) -> usize {
+    pallet
+        .iter()
+        .rev()
+        .zip(target.iter().rev())
+        .take_while(|(current, wanted)| current == wanted)
+        .count()
+}
+
+/// Stack to dump unsettled crates on, preferring ones that are already cluttered and low
+fn buffer(
+    pallets: &[VecDeque<char>],
+    target: &[VecDeque<char>],
+    excluded: [usize; 2],
+) -> Option<usize> {
+    (0..pallets.len())
+        .filter(|i| !excluded.contains(i))
+        .min_by_key(|&i| {
+            let clean = settled(&pallets[i], &target[i]) == pallets[i].len();
+            (clean, pallets[i].len())
+        })
+}
+
+/// Greedily builds every target stack bottom-up: picks the cheapest next crate to settle,
+/// clears the stacks above it and on its destination, then moves as many crates at once as
+/// the crane can settle. When every crate still needed is buried in its own stack, that
+/// stack is cleared onto a buffer first. Fails only when the stacks hold different crates,
+/// or when there are too few stacks to set any aside
+fn plan(
+    crane: &dyn Crane,
+    start: &[VecDeque<char>],
+    target: &[VecDeque<char>],
+) -> Option<Vec<Step>> {
+    let mut sorted_start = start.iter().flatten().collect::<Vec<_>>();
+    let mut sorted_target = target.iter().flatten().collect::<Vec<_>>();
+    sorted_start.sort_unstable();
+    sorted_target.sort_unstable();
+    if start.len() != target.len() || sorted_start != sorted_target {
+        return None;
+    }
+
+    let mut pallets = start.to_vec();
+    let mut plan = Vec::new();
+    let mut lift = |pallets: &mut Vec<VecDeque<char>>, step: Step| {
+        if step.0 > 0 {
+            crane.lift(pallets, step);
+            plan.push(step);
+        }
+    };
+    while pallets != target {
+        let settled_counts = (0..pallets.len())
+            .map(|i| settled(&pallets[i], &target[i]))
+            .collect::<Vec<_>>();
+        let unsettled = (0..pallets.len())
+            .filter(|&to| settled_counts[to] < target[to].len())
+            .collect::<Vec<_>>();
+        let candidate = unsettled
+            .iter()
+            .flat_map(|&to| {
+                let needed = target[to][target[to].len() - settled_counts[to] - 1];
+                let clutter = pallets[to].len() - settled_counts[to];
+                let pallets = &pallets;
+                let settled_counts = &settled_counts;
+                (0..pallets.len())
+                    .filter(move |&from| from != to)
+                    .filter_map(move |from| {
+                        let loose = pallets[from].len() - settled_counts[from];
+                        let depth = pallets[from]
+                            .iter()
+                            .take(loose)
+                            .position(|&cargo| cargo == needed)?;
+                        Some((clutter + depth, (to, from, depth)))
+                    })
+            })
+            .min();
+        let Some((_, (to, from, depth))) = candidate else {
+            // The crate `to` needs next is under its own loose crates, set them aside and
+            // look again with the buffer included
+            let to = *unsettled.first()?;
+            let clutter = pallets[to].len() - settled_counts[to];
+            let spare = buffer(&pallets, target, [to, to])?;
+            lift(&mut pallets, (clutter, to, spare));
+            continue;
+        };
+
+        let clutter = pallets[to].len() - settled_counts[to];
+        if clutter > 0 {
+            let spare = buffer(&pallets, target, [to, from])?;
+            lift(&mut pallets, (clutter, to, spare));
+        }
+        if depth > 0 {
+            let spare = buffer(&pallets, target, [to, from])?;
+            lift(&mut pallets, (depth, from, spare));
         }
+        let loose = pallets[from].len() - settled(&pallets[from], &target[from]);
+        let count = (1..=loose)
+            .rev()
+            .find(|&count| {
+                let mut attempt = pallets.clone();
+                crane.lift(&mut attempt, (count, from, to));
+                settled(&attempt[to], &target[to]) == settled_counts[to] + count
+            })
+            .unwrap_or(1);
+        lift(&mut pallets, (count, from, to));
+    }
+    Some(plan)
+}
+
+fn operate(crane: &dyn Crane, layers: &[Slots], steps: &[Step]) -> String {
+    let mut pallets = fill_pallets(layers);
+    for &step in steps {
+        crane.lift(&mut pallets, step);
     }
     pallets
         .iter()
         .map(|pallet| pallet.front().unwrap())
         .collect()
 }
+
+fn part1(layers: &[Slots], steps: &[Step]) -> String {
+    operate(&CrateMover9000, layers, steps)
+}
+
+fn part2(layers: &[Slots], steps: &[Step]) -> String {
+    operate(&CrateMover9001, layers, steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stacks written top crate first
+    fn stacks(stacks: &[&str]) -> Vec<VecDeque<char>> {
+        stacks.iter().map(|stack| stack.chars().collect()).collect()
+    }
+
+    fn cranes() -> Vec<Box<dyn Crane>> {
+        vec![
+            Box::new(CrateMover9000),
+            Box::new(CrateMover9001),
+            Box::new(Capped { capacity: 2 }),
+        ]
+    }
+
+    fn assert_plan_reaches(crane: &dyn Crane, start: &[VecDeque<char>], target: &[VecDeque<char>]) {
+        let plan = plan(crane, start, target).expect("target is reachable");
+        let mut pallets = start.to_vec();
+        validate(&pallets, &plan).unwrap();
+        for step in plan {
+            crane.lift(&mut pallets, step);
+        }
+        assert_eq!(pallets, target);
+    }
+
+    #[test]
+    fn plans_when_the_next_crate_is_buried_in_its_own_stack() {
+        for crane in cranes() {
+            assert_plan_reaches(
+                crane.as_ref(),
+                &stacks(&["AB", "", ""]),
+                &stacks(&["BA", "", ""]),
+            );
+            assert_plan_reaches(
+                crane.as_ref(),
+                &stacks(&["A", "BD", "CE"]),
+                &stacks(&["", "A", "BCDE"]),
+            );
+        }
+    }
+
+    /// Drops the crates on random stacks, each on top of the ones before
+    fn scatter(rng: &mut fastrand::Rng, cargo: &[char], count: usize) -> Vec<VecDeque<char>> {
+        let mut pallets = vec![VecDeque::new(); count];
+        for &cargo in cargo {
+            pallets[rng.usize(..count)].push_front(cargo);
+        }
+        pallets
+    }
+
+    #[test]
+    fn plans_random_shuffles() {
+        let mut rng = fastrand::Rng::with_seed(5);
+        for _ in 0..2_000 {
+            let count = rng.usize(3..6);
+            let cargo = (0..rng.usize(..9))
+                .map(|_| rng.char('A'..='E'))
+                .collect::<Vec<_>>();
+            let start = scatter(&mut rng, &cargo, count);
+            let mut shuffled = cargo.clone();
+            rng.shuffle(&mut shuffled);
+            let target = scatter(&mut rng, &shuffled, count);
+            for crane in cranes() {
+                assert_plan_reaches(crane.as_ref(), &start, &target);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_different_crates() {
+        assert!(plan(&CrateMover9000, &stacks(&["AB", ""]), &stacks(&["A", "C"])).is_none());
+        assert!(plan(&CrateMover9000, &stacks(&["AB", ""]), &stacks(&["AB"])).is_none());
+    }
+}
@@ -1,27 +1,232 @@
-use std::collections::HashSet;
+use std::{
+    io::{self, BufReader, Read},
+    ops::Range,
+};
 
 fn main() {
     let buffer = parse(include_str!("in").trim());
-    dbg!(part1(&buffer));
-    dbg!(part2(&buffer));
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["marker", size, ..] if size.parse::<usize>().ok() == Some(0) => {
+            eprintln!("size must be at least 1");
+            std::process::exit(1);
+        }
+        ["decode", ref sizes @ ..]
+            if sizes
+                .iter()
+                .any(|size| size.parse::<usize>().ok() == Some(0)) =>
+        {
+            eprintln!("size must be at least 1");
+            std::process::exit(1);
+        }
+        ["marker", size] => {
+            dbg!(find_marker_in(io::stdin().lock(), size.parse().unwrap()).unwrap());
+        }
+        ["marker", size, path] => {
+            let file = std::fs::File::open(path).unwrap();
+            dbg!(find_marker_in(file, size.parse().unwrap()).unwrap());
+        }
+        ["decode", ref sizes @ ..] => {
+            let sizes = match sizes {
+                [] => vec![4, 14],
+                sizes => sizes.iter().map(|size| size.parse().unwrap()).collect(),
+            };
+            for decoded in decode(buffer, &sizes) {
+                println!(
+                    "window {}: {} markers",
+                    decoded.size,
+                    decoded.segments.len()
+                );
+                for segment in &decoded.segments {
+                    println!(
+                        "  marker at {}, payload {:?} {:?}",
+                        segment.marker,
+                        segment.payload,
+                        String::from_utf8_lossy(&buffer[segment.payload.clone()])
+                    );
+                }
+            }
+        }
+        _ => {
+            dbg!(part1(buffer));
+            dbg!(part2(buffer));
+        }
+    }
 }
 
-fn parse(input: &str) -> Vec<char> {
-    input.chars().collect()
+fn parse(input: &str) -> &[u8] {
+    input.as_bytes()
 }
 
-fn part1(buffer: &[char]) -> Option<usize> {
-    find_unique_slice::<4>(buffer)
+fn part1(buffer: &[u8]) -> Option<usize> {
+    find_marker(buffer, 4)
 }
 
-fn part2(buffer: &[char]) -> Option<usize> {
-    find_unique_slice::<14>(buffer)
+fn part2(buffer: &[u8]) -> Option<usize> {
+    find_marker(buffer, 14)
 }
 
-fn find_unique_slice<const SIZE: usize>(buffer: &[char]) -> Option<usize> {
-    buffer
-        .windows(SIZE)
+/// Sliding window over the last `size` bytes that tracks how many of them repeat
+struct MarkerDetector {
+    window: Vec<u8>,
+    counts: [usize; 256],
+    repeated: usize,
+    position: usize,
+}
+
+impl MarkerDetector {
+    fn new(size: usize) -> Self {
+        assert!(size > 0, "window size must be positive");
+        Self {
+            window: vec![0; size],
+            counts: [0; 256],
+            repeated: 0,
+            position: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.counts = [0; 256];
+        self.repeated = 0;
+        self.position = 0;
+    }
+
+    /// Feeds the next byte, returns the number of bytes read so far
+    /// if the last `size` of them are all different
+    fn push(&mut self, byte: u8) -> Option<usize> {
+        let size = self.window.len();
+        let slot = self.position % size;
+        if self.position >= size {
+            let old = self.window[slot] as usize;
+            self.counts[old] -= 1;
+            if self.counts[old] == 1 {
+                self.repeated -= 1;
+            }
+        }
+        self.window[slot] = byte;
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 2 {
+            self.repeated += 1;
+        }
+        self.position += 1;
+
+        if self.position >= size && self.repeated == 0 {
+            Some(self.position)
+        } else {
+            None
+        }
+    }
+}
+
+fn find_marker(buffer: &[u8], size: usize) -> Option<usize> {
+    let mut detector = MarkerDetector::new(size);
+    buffer.iter().find_map(|&byte| detector.push(byte))
+}
+
+fn find_marker_in(reader: impl Read, size: usize) -> io::Result<Option<usize>> {
+    let mut detector = MarkerDetector::new(size);
+    for byte in BufReader::new(reader).bytes() {
+        if let Some(position) = detector.push(byte?) {
+            return Ok(Some(position));
+        }
+    }
+    Ok(None)
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+    /// Position right after the marker, as reported by the puzzle
+    marker: usize,
+    /// Bytes between this marker and the start of the next one
+    payload: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Decoded {
+    size: usize,
+    segments: Vec<Segment>,
+}
+
+/// Splits the stream on every non-overlapping marker of `size` distinct bytes,
+/// the search restarts right after each marker it finds
+fn split(buffer: &[u8], size: usize) -> Decoded {
+    let mut detector = MarkerDetector::new(size);
+    let mut markers = Vec::new();
+    for (i, &byte) in buffer.iter().enumerate() {
+        if detector.push(byte).is_some() {
+            markers.push(i + 1);
+            detector.reset();
+        }
+    }
+    let segments = markers
+        .iter()
         .enumerate()
-        .find(|(_, chunk)| chunk.iter().collect::<HashSet<_>>().len() == SIZE)
-        .map(|(i, _)| i + SIZE)
+        .map(|(i, &marker)| {
+            let end = markers.get(i + 1).map_or(buffer.len(), |next| next - size);
+            Segment {
+                marker,
+                payload: marker..end,
+            }
+        })
+        .collect();
+    Decoded { size, segments }
+}
+
+fn decode(buffer: &[u8], sizes: &[usize]) -> Vec<Decoded> {
+    sizes.iter().map(|&size| split(buffer, size)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    /// The windowed scan the detector replaced
+    fn find_marker_naive(buffer: &[u8], size: usize) -> Option<usize> {
+        buffer
+            .windows(size)
+            .position(|window| window.iter().collect::<HashSet<_>>().len() == size)
+            .map(|start| start + size)
+    }
+
+    /// Markers and payloads found by restarting the windowed scan after every marker
+    fn split_naive(buffer: &[u8], size: usize) -> Vec<(usize, Range<usize>)> {
+        let mut markers = Vec::new();
+        let mut start = 0;
+        while let Some(found) = find_marker_naive(&buffer[start..], size) {
+            start += found;
+            markers.push(start);
+        }
+        (0..markers.len())
+            .map(|i| {
+                let end = markers.get(i + 1).map_or(buffer.len(), |next| next - size);
+                (markers[i], markers[i]..end)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detector_matches_the_windowed_scan() {
+        let mut rng = fastrand::Rng::with_seed(6);
+        for _ in 0..5_000 {
+            let letters = rng.u8(1..8);
+            let buffer = (0..rng.usize(..60))
+                .map(|_| b'a' + rng.u8(..letters))
+                .collect::<Vec<_>>();
+            let size = rng.usize(1..7);
+
+            assert_eq!(find_marker(&buffer, size), find_marker_naive(&buffer, size));
+            assert_eq!(
+                find_marker_in(&buffer[..], size).unwrap(),
+                find_marker_naive(&buffer, size)
+            );
+            let segments = split(&buffer, size)
+                .segments
+                .into_iter()
+                .map(|segment| (segment.marker, segment.payload))
+                .collect::<Vec<_>>();
+            assert_eq!(segments, split_naive(&buffer, size));
+        }
+    }
 }
@@ -0,0 +1,260 @@
+use serde::Serialize;
+
+use crate::{Command, Output};
+
+pub type NodeId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Dir,
+    File(u128),
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: String,
+    pub kind: Kind,
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+}
+
+/// Directory tree reconstructed from a shell transcript, nodes live in an arena indexed by `NodeId`
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    nodes: Vec<Node>,
+    /// Total size under each node, filled in once the transcript is read
+    sizes: Vec<u128>,
+}
+
+impl FileSystem {
+    pub const ROOT: NodeId = 0;
+
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                name: "/".to_owned(),
+                kind: Kind::Dir,
+                parent: None,
+                children: Vec::new(),
+            }],
+            sizes: vec![0],
+        }
+    }
+
+    pub fn from_commands(commands: &[Command]) -> Self {
+        let mut fs = Self::new();
+        let mut cwd = Self::ROOT;
+        for command in commands {
+            match command {
+                Command::Cd(path) => {
+                    if path.starts_with('/') {
+                        cwd = Self::ROOT;
+                    }
+                    for part in path.split('/').filter(|part| !part.is_empty()) {
+                        cwd = match part {
+                            "." => cwd,
+                            ".." => fs.parent(cwd).unwrap_or(Self::ROOT),
+                            name => fs.insert(cwd, name, Kind::Dir),
+                        };
+                    }
+                }
+                Command::Ls(output) => {
+                    for out in output {
+                        match *out {
+                            Output::Dir(name) => fs.insert(cwd, name, Kind::Dir),
+                            Output::File(size, name) => fs.insert(cwd, name, Kind::File(size)),
+                        };
+                    }
+                }
+            }
+        }
+        fs.compute_sizes();
+        fs
+    }
+
+    /// Sums sizes bottom-up, children are always created after their parent so a reverse
+    /// sweep over the arena sees every subtree before the directory holding it
+    fn compute_sizes(&mut self) {
+        self.sizes = self
+            .nodes
+            .iter()
+            .map(|node| match node.kind {
+                Kind::File(size) => size,
+                Kind::Dir => 0,
+            })
+            .collect();
+        for id in (1..self.nodes.len()).rev() {
+            if let Some(parent) = self.nodes[id].parent {
+                self.sizes[parent] += self.sizes[id];
+            }
+        }
+    }
+
+    /// Returns the existing child called `name` or creates it, a listed file keeps its latest size.
+    /// A name reported both as a directory and as a file stays a directory, so a subtree
+    /// already read is never hidden behind a file
+    fn insert(&mut self, parent: NodeId, name: &str, kind: Kind) -> NodeId {
+        if let Some(id) = self.child(parent, name) {
+            match (self.nodes[id].kind, kind) {
+                (Kind::File(_), Kind::File(_)) => self.nodes[id].kind = kind,
+                (Kind::File(_), Kind::Dir) => self.nodes[id].kind = Kind::Dir,
+                (Kind::Dir, _) => {}
+            }
+            return id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_owned(),
+            kind,
+            parent: Some(parent),
+            children: Vec::new(),
+        });
+        self.nodes[parent].children.push(id);
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn is_dir(&self, id: NodeId) -> bool {
+        self.nodes[id].kind == Kind::Dir
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id].children
+    }
+
+    pub fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.children(id)
+            .iter()
+            .copied()
+            .find(|&child| self.nodes[child].name == name)
+    }
+
+    /// Resolves a `/`-separated path from the root, `.` and `..` are allowed
+    pub fn lookup(&self, path: &str) -> Option<NodeId> {
+        path.split('/').filter(|part| !part.is_empty()).try_fold(
+            Self::ROOT,
+            |id, part| match part {
+                "." => Some(id),
+                ".." => Some(self.parent(id).unwrap_or(Self::ROOT)),
+                name => self.child(id, name),
+            },
+        )
+    }
+
+    pub fn path(&self, id: NodeId) -> String {
+        let mut parts = Vec::new();
+        let mut current = id;
+        while let Some(parent) = self.parent(current) {
+            parts.push(self.nodes[current].name.as_str());
+            current = parent;
+        }
+        parts.reverse();
+        format!("/{}", parts.join("/"))
+    }
+
+    /// Total size of the files under `id`
+    pub fn size(&self, id: NodeId) -> u128 {
+        self.sizes[id]
+    }
+
+    /// All nodes matching `predicate`, in depth-first order
+    pub fn find(&self, predicate: impl Fn(&Self, NodeId) -> bool) -> Vec<NodeId> {
+        let mut found = Vec::new();
+        let mut stack = vec![Self::ROOT];
+        while let Some(id) = stack.pop() {
+            if predicate(self, id) {
+                found.push(id);
+            }
+            stack.extend(self.children(id).iter().rev());
+        }
+        found
+    }
+
+    /// Directories at most `max_depth` levels below the root with their sizes, largest first
+    pub fn du(&self, max_depth: Option<usize>) -> Vec<(NodeId, u128)> {
+        let mut usage = Vec::new();
+        let mut stack = vec![(Self::ROOT, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            if max_depth.is_some_and(|max_depth| depth > max_depth) {
+                continue;
+            }
+            usage.push((id, self.size(id), self.path(id)));
+            for &child in self.children(id) {
+                if self.is_dir(child) {
+                    stack.push((child, depth + 1));
+                }
+            }
+        }
+        usage.sort_unstable_by(|(_, a_size, a_path), (_, b_size, b_path)| {
+            b_size.cmp(a_size).then_with(|| a_path.cmp(b_path))
+        });
+        usage.into_iter().map(|(id, size, _)| (id, size)).collect()
+    }
+
+    pub fn to_json(&self, id: NodeId) -> String {
+        serde_json::to_string_pretty(&self.json_node(id)).unwrap()
+    }
+
+    fn json_node(&self, id: NodeId) -> JsonNode<'_> {
+        let node = &self.nodes[id];
+        JsonNode {
+            name: &node.name,
+            kind: match node.kind {
+                Kind::Dir => "dir",
+                Kind::File(_) => "file",
+            },
+            size: self.size(id),
+            children: node
+                .children
+                .iter()
+                .map(|&child| self.json_node(child))
+                .collect(),
+        }
+    }
+
+    /// Draws the subtree under `id` like `tree` does
+    pub fn render(&self, id: NodeId) -> String {
+        let mut lines = vec![self.label(id)];
+        self.render_children(id, "", &mut lines);
+        lines.join("\n") + "\n"
+    }
+
+    fn render_children(&self, id: NodeId, prefix: &str, lines: &mut Vec<String>) {
+        let children = self.children(id);
+        for (i, &child) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            lines.push(format!("{prefix}{branch}{}", self.label(child)));
+            self.render_children(child, &format!("{prefix}{indent}"), lines);
+        }
+    }
+
+    fn label(&self, id: NodeId) -> String {
+        let node = &self.nodes[id];
+        match node.kind {
+            Kind::Dir => format!("{}/ ({})", node.name.trim_end_matches('/'), self.size(id)),
+            Kind::File(size) => format!("{} ({size})", node.name),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JsonNode<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    size: u128,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<JsonNode<'a>>,
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// `cd` into each directory right after listing it and `cd ..` back out, like the puzzle
+    DepthFirst,
+    /// List level by level, jumping between directories with absolute `cd` paths
+    BreadthFirst,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub order: Order,
+    /// Directories deeper than this are listed by their parent but never entered
+    pub max_depth: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            order: Order::DepthFirst,
+            max_depth: None,
+        }
+    }
+}
+
+enum Entry {
+    Dir(String),
+    File(u64, String),
+}
+
+/// Sorted directory entries, skipping symlinks and names the transcript cannot hold
+fn entries(path: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+            continue;
+        };
+        if name.contains(['\n', '\r']) {
+            continue;
+        }
+        let metadata = entry.path().symlink_metadata()?;
+        if metadata.is_dir() {
+            entries.push(Entry::Dir(name));
+        } else if metadata.is_file() {
+            entries.push(Entry::File(metadata.len(), name));
+        }
+    }
+    entries.sort_by(|a, b| name(a).cmp(name(b)));
+    Ok(entries)
+}
+
+fn name(entry: &Entry) -> &str {
+    match entry {
+        Entry::Dir(name) | Entry::File(_, name) => name,
+    }
+}
+
+fn ls(transcript: &mut String, entries: &[Entry]) {
+    transcript.push_str("$ ls\n");
+    for entry in entries {
+        match entry {
+            Entry::Dir(name) => transcript.push_str(&format!("dir {name}\n")),
+            Entry::File(size, name) => transcript.push_str(&format!("{size} {name}\n")),
+        }
+    }
+}
+
+fn depth_first(
+    transcript: &mut String,
+    path: &Path,
+    depth: usize,
+    options: &Options,
+) -> io::Result<()> {
+    let entries = entries(path)?;
+    ls(transcript, &entries);
+    if options
+        .max_depth
+        .is_some_and(|max_depth| depth >= max_depth)
+    {
+        return Ok(());
+    }
+    for entry in &entries {
+        if let Entry::Dir(name) = entry {
+            transcript.push_str(&format!("$ cd {name}\n"));
+            depth_first(transcript, &path.join(name), depth + 1, options)?;
+            transcript.push_str("$ cd ..\n");
+        }
+    }
+    Ok(())
+}
+
+fn breadth_first(transcript: &mut String, root: &Path, options: &Options) -> io::Result<()> {
+    let mut queue = VecDeque::from([(PathBuf::new(), 0)]);
+    while let Some((relative, depth)) = queue.pop_front() {
+        let entries = entries(&root.join(&relative))?;
+        transcript.push_str(&format!("$ cd /{}\n", relative.display()));
+        ls(transcript, &entries);
+        if options
+            .max_depth
+            .is_some_and(|max_depth| depth >= max_depth)
+        {
+            continue;
+        }
+        for entry in &entries {
+            if let Entry::Dir(name) = entry {
+                queue.push_back((relative.join(name), depth + 1));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Shell transcript exploring `root` in the format `parse` reads
+pub fn transcript(root: &Path, options: &Options) -> io::Result<String> {
+    let mut transcript = String::new();
+    match options.order {
+        Order::DepthFirst => {
+            transcript.push_str("$ cd /\n");
+            depth_first(&mut transcript, root, 0, options)?;
+        }
+        Order::BreadthFirst => breadth_first(&mut transcript, root, options)?,
+    }
+    Ok(transcript)
+}
+
+/// Apparent size of every directory under `root` keyed by its transcript path, like `du -b`
+/// limited to the files the transcript can see
+pub fn disk_usage(root: &Path, options: &Options) -> io::Result<BTreeMap<String, u128>> {
+    fn walk(
+        path: &Path,
+        name: String,
+        depth: usize,
+        options: &Options,
+        usage: &mut BTreeMap<String, u128>,
+    ) -> io::Result<u128> {
+        let mut size = 0;
+        let entered = options.max_depth.is_none_or(|max_depth| depth <= max_depth);
+        if entered {
+            for entry in entries(path)? {
+                size += match entry {
+                    Entry::File(file_size, _) => file_size as u128,
+                    Entry::Dir(child) => {
+                        let child_name = format!("{}/{child}", name.trim_end_matches('/'));
+                        walk(&path.join(&child), child_name, depth + 1, options, usage)?
+                    }
+                };
+            }
+        }
+        usage.insert(name, size);
+        Ok(size)
+    }
+
+    let mut usage = BTreeMap::new();
+    walk(root, "/".to_owned(), 0, options, &mut usage)?;
+    Ok(usage)
+}
@@ -1,23 +1,152 @@
-use std::{collections::VecDeque, path::Path};
+use std::path::Path;
 
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while},
+    bytes::complete::{tag, take_till1},
     character::{
         self,
-        complete::{alpha1, newline},
-        streaming::line_ending,
+        complete::{line_ending, multispace0},
     },
     combinator::{all_consuming, map},
-    multi::separated_list1,
+    multi::{many0, separated_list1},
     sequence::{preceded, separated_pair, terminated},
     IResult,
 };
 
+mod fs;
+mod generate;
+
+use fs::{FileSystem, NodeId};
+
 fn main() {
     let (_, commands) = parse(include_str!("in")).unwrap();
-    dbg!(part1(&commands));
-    dbg!(part2(&commands));
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["tree", ref path @ ..] => {
+            let fs = FileSystem::from_commands(&commands);
+            let id = fs
+                .lookup(path.first().unwrap_or(&"/"))
+                .expect("no such path");
+            print!("{}", fs.render(id));
+        }
+        ["ls", path] => {
+            let fs = FileSystem::from_commands(&commands);
+            let id = fs.lookup(path).expect("no such path");
+            for &child in fs.children(id) {
+                let kind = if fs.is_dir(child) { "dir" } else { "file" };
+                println!("{kind:>4} {:>10} {}", fs.size(child), fs.node(child).name);
+            }
+        }
+        ["find", min_size] => {
+            let fs = FileSystem::from_commands(&commands);
+            let min_size = min_size.parse::<u128>().unwrap();
+            for id in fs.find(|fs, id| fs.is_dir(id) && fs.size(id) >= min_size) {
+                println!("{:>10} {}", fs.size(id), fs.path(id));
+            }
+        }
+        ["cleanup", ref sizes @ ..] => {
+            let disk = match sizes {
+                [capacity, required] => Disk {
+                    capacity: capacity.parse().unwrap(),
+                    required: required.parse().unwrap(),
+                },
+                _ => Disk::default(),
+            };
+            print!(
+                "{}",
+                explain_cleanup(&FileSystem::from_commands(&commands), &disk)
+            );
+        }
+        ["generate", root, ref options @ ..] => {
+            let options = generate_options(options);
+            print!(
+                "{}",
+                generate::transcript(Path::new(root), &options).unwrap()
+            );
+        }
+        ["verify", root, ref options @ ..] => {
+            let options = generate_options(options);
+            let transcript = generate::transcript(Path::new(root), &options).unwrap();
+            let (_, commands) = parse(&transcript).unwrap();
+            let expected = generate::disk_usage(Path::new(root), &options).unwrap();
+            let directories = build_directories(&commands);
+            let mut mismatches = 0;
+            for directory in &directories {
+                let wanted = expected.get(&directory.name).copied();
+                if wanted != Some(directory.size) {
+                    mismatches += 1;
+                    println!("{}: {} vs du {wanted:?}", directory.name, directory.size);
+                }
+            }
+            if directories.len() != expected.len() {
+                mismatches += 1;
+                println!("{} directories vs du {}", directories.len(), expected.len());
+            }
+            println!("{} directories, {mismatches} mismatches", directories.len());
+            if mismatches > 0 {
+                std::process::exit(1);
+            }
+        }
+        ["du", ref depth @ ..] => {
+            let max_depth = depth.first().map(|depth| depth.parse().unwrap());
+            let fs = FileSystem::from_commands(&commands);
+            for (id, size) in fs.du(max_depth) {
+                println!("{}\t{}", human_size(size), fs.path(id));
+            }
+        }
+        ["json", ref path @ ..] => {
+            let fs = FileSystem::from_commands(&commands);
+            let id = fs
+                .lookup(path.first().unwrap_or(&"/"))
+                .expect("no such path");
+            println!("{}", fs.to_json(id));
+        }
+        _ => {
+            dbg!(part1(&commands));
+            dbg!(part2(&commands, &Disk::default()));
+        }
+    }
+}
+
+/// Reads `--bfs` and `--depth N` after the directory to generate a transcript from
+fn generate_options(args: &[&str]) -> generate::Options {
+    let mut options = generate::Options::default();
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        match arg {
+            "--bfs" => options.order = generate::Order::BreadthFirst,
+            "--dfs" => options.order = generate::Order::DepthFirst,
+            "--depth" => options.max_depth = args.next().and_then(|depth| depth.parse().ok()),
+            _ => panic!("unknown option {arg}"),
+        }
+    }
+    options
+}
+
+/// Size in powers of 1024 the way `du -h` prints it: one decimal below 10, rounded up.
+/// Rounding happens before picking the unit, so 1048575 carries over to `1.0M`
+fn human_size(size: u128) -> String {
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut value = size as f64;
+    for unit in ["K", "M", "G", "T", "P"] {
+        value /= 1024.0;
+        let tenths = (value * 10.0).ceil();
+        let rounded = if tenths < 100.0 {
+            tenths / 10.0
+        } else {
+            value.ceil()
+        };
+        if rounded < 1024.0 || unit == "P" {
+            return if tenths < 100.0 {
+                format!("{rounded:.1}{unit}")
+            } else {
+                format!("{rounded}{unit}")
+            };
+        }
+    }
+    unreachable!()
 }
 
 #[derive(Debug, Clone)]
@@ -26,19 +155,17 @@ enum Command<'a> {
     Ls(Vec<Output<'a>>),
 }
 
-fn cd(input: &str) -> IResult<&str, Command> {
-    map(
-        preceded(
-            tag("$ cd "),
-            take_while(|c: char| c == '/' || c == '.' || c.is_ascii_alphabetic()),
-        ),
-        Command::Cd,
-    )(input)
+/// Anything up to the end of the line, so names may hold digits, dashes, spaces and so on
+fn name(input: &str) -> IResult<&str, &str> {
+    take_till1(|c: char| c == '\n' || c == '\r')(input)
 }
 
-fn ls(input: &str) -> IResult<&str, Command> {
+fn cd(input: &str) -> IResult<&str, Command<'_>> {
+    map(preceded(tag("$ cd "), name), Command::Cd)(input)
+}
+
+fn ls(input: &str) -> IResult<&str, Command<'_>> {
     let (input, _) = tag("$ ls")(input)?;
-    let (input, _) = newline(input)?;
     let (input, output) = output(input)?;
     Ok((input, Command::Ls(output)))
 }
@@ -50,35 +177,32 @@ enum Output<'a> {
 }
 
 fn dir(input: &str) -> IResult<&str, &str> {
-    preceded(tag("dir "), alpha1)(input)
+    preceded(tag("dir "), name)(input)
 }
 
 fn file(input: &str) -> IResult<&str, (u128, &str)> {
-    separated_pair(
-        character::complete::u128,
-        tag(" "),
-        take_while(|c: char| c == '.' || c.is_ascii_alphabetic()),
-    )(input)
+    separated_pair(character::complete::u128, tag(" "), name)(input)
 }
 
-fn output(input: &str) -> IResult<&str, Vec<Output>> {
-    separated_list1(
+/// Listing lines after `$ ls`, an empty directory has none
+fn output(input: &str) -> IResult<&str, Vec<Output<'_>>> {
+    many0(preceded(
         line_ending,
         alt((
             map(dir, Output::Dir),
             map(file, |(size, name)| Output::File(size, name)),
         )),
-    )(input)
+    ))(input)
 }
 
-fn command(input: &str) -> IResult<&str, Command> {
+fn command(input: &str) -> IResult<&str, Command<'_>> {
     alt((cd, ls))(input)
 }
 
-fn parse(input: &str) -> IResult<&str, Vec<Command>> {
+fn parse(input: &str) -> IResult<&str, Vec<Command<'_>>> {
     all_consuming(terminated(
         separated_list1(line_ending, command),
-        line_ending,
+        multispace0,
     ))(input)
 }
 
@@ -89,52 +213,186 @@ struct Directory {
 }
 
 fn build_directories(commands: &[Command]) -> Vec<Directory> {
-    let mut stack = VecDeque::<Directory>::new();
-    let mut directories = Vec::<Directory>::new();
-    for command in commands {
-        match command {
-            Command::Ls(output) => {
-                let files: u128 = output
-                    .iter()
-                    .filter_map(|&out| match out {
-                        Output::Dir(_) => None,
-                        Output::File(size, _) => Some(size),
-                    })
-                    .sum();
-                if let Some(parent) = stack.back_mut() {
-                    parent.size += files;
-                }
+    let fs = FileSystem::from_commands(commands);
+    fs.find(|fs, id| fs.is_dir(id))
+        .into_iter()
+        .map(|id| Directory {
+            name: fs.path(id),
+            size: fs.size(id),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Disk {
+    capacity: u128,
+    required: u128,
+}
+
+impl Default for Disk {
+    fn default() -> Self {
+        Self {
+            capacity: 70_000_000,
+            required: 30_000_000,
+        }
+    }
+}
+
+impl Disk {
+    /// How much has to be deleted before the update fits
+    fn overflow(&self, used: u128) -> u128 {
+        (used + self.required).saturating_sub(self.capacity)
+    }
+}
+
+/// Directories below the root in depth-first order, each with the position just past its subtree
+fn cleanup_order(fs: &FileSystem, id: NodeId, order: &mut Vec<(NodeId, usize)>) {
+    let position = order.len();
+    if id != FileSystem::ROOT {
+        order.push((id, 0));
+    }
+    for &child in fs.children(id).iter().filter(|&&child| fs.is_dir(child)) {
+        cleanup_order(fs, child, order);
+    }
+    if id != FileSystem::ROOT {
+        order[position].1 = order.len();
+    }
+}
+
+/// Most distinct partial totals the cleanup planner keeps at once
+const CLEANUP_TOTALS: usize = 1 << 20;
+
+/// Most partial totals the cleanup planner looks at overall
+const CLEANUP_WORK: usize = 1 << 27;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CleanupError {
+    /// Even deleting every directory leaves too little space
+    Unreachable,
+    /// The directories add up to too many distinct totals to search them all
+    OverBudget,
+}
+
+impl std::fmt::Display for CleanupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unreachable => write!(f, "no set of directories frees enough space"),
+            Self::OverBudget => write!(
+                f,
+                "too many ways to combine directories, gave up on finding the smallest set"
+            ),
+        }
+    }
+}
+
+/// Non-nested directories with the smallest total size that free enough space for the update.
+///
+/// Walking directories in depth-first order, each one is either skipped or deleted along
+/// with its whole subtree, so the suffix starting at every position has a set of totals it
+/// can free. Suffixes starting earlier can free everything later ones can, so each total
+/// below the target is kept once with the last position it is reachable from. Of the totals
+/// reaching the target only the smallest matters
+fn cleanup(fs: &FileSystem, disk: &Disk) -> Result<Vec<NodeId>, CleanupError> {
+    let target = disk.overflow(fs.size(FileSystem::ROOT));
+    if target == 0 {
+        return Ok(Vec::new());
+    }
+    let mut order = Vec::new();
+    cleanup_order(fs, FileSystem::ROOT, &mut order);
+    let sizes = order.iter().map(|&(id, _)| fs.size(id)).collect::<Vec<_>>();
+
+    let n = order.len();
+    // Sorted by total, paired with the last position whose suffix can free it
+    let mut below: Vec<(u128, usize)> = vec![(0, n)];
+    let mut reaching: Vec<Option<u128>> = vec![None; n + 1];
+    let mut work = 0;
+    for k in (0..n).rev() {
+        let (_, end) = order[k];
+        let size = sizes[k];
+        work += below.len();
+        if work > CLEANUP_WORK {
+            return Err(CleanupError::OverBudget);
+        }
+
+        let mut deleted = Vec::new();
+        let mut crossing = None;
+        for &(total, last) in &below {
+            if last < end {
+                continue;
             }
-            Command::Cd(dir) => match *dir {
-                ".." => {
-                    if let Some(child) = stack.pop_back() {
-                        if let Some(parent) = stack.back_mut() {
-                            parent.size += child.size;
-                        }
-                        directories.push(child);
-                    }
-                }
-                name => {
-                    let name = Path::new("")
-                        .join(stack.back().map_or("", |d| &d.name))
-                        .join(name)
-                        .to_str()
-                        .unwrap()
-                        .to_owned();
-                    stack.push_back(Directory { name, size: 0 })
-                }
-            },
+            if total + size >= target {
+                crossing = Some(total + size);
+                break;
+            }
+            deleted.push(total + size);
+        }
+        reaching[k] = [
+            reaching[k + 1],
+            reaching[end].map(|total| total + size),
+            crossing,
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+
+        let mut merged = Vec::with_capacity(below.len() + deleted.len());
+        let mut deleted = deleted.into_iter().peekable();
+        for &(total, last) in &below {
+            while let Some(new) = deleted.next_if(|&new| new < total) {
+                merged.push((new, k));
+            }
+            deleted.next_if_eq(&total);
+            merged.push((total, last));
+        }
+        merged.extend(deleted.map(|new| (new, k)));
+        below = merged;
+        if below.len() > CLEANUP_TOTALS {
+            return Err(CleanupError::OverBudget);
         }
     }
 
-    while let Some(child) = stack.pop_back() {
-        if let Some(parent) = stack.back_mut() {
-            parent.size += child.size;
+    let mut left = reaching[0].ok_or(CleanupError::Unreachable)?;
+    let mut dirs = Vec::new();
+    let mut k = 0;
+    while k < n {
+        let (id, end) = order[k];
+        let skip = if left >= target {
+            reaching[k + 1] == Some(left)
+        } else {
+            let i = below.partition_point(|&(total, _)| total < left);
+            below[i].1 > k
+        };
+        if skip {
+            k += 1;
+        } else {
+            dirs.push(id);
+            left -= sizes[k];
+            k = end;
         }
-        directories.push(child);
     }
+    Ok(dirs)
+}
 
-    directories
+fn explain_cleanup(fs: &FileSystem, disk: &Disk) -> String {
+    let used = fs.size(FileSystem::ROOT);
+    let target = disk.overflow(used);
+    let mut text = format!(
+        "used {used} of {}, the update needs {} free, so {target} must go\n",
+        disk.capacity, disk.required
+    );
+    match cleanup(fs, disk) {
+        Err(error) => text += &format!("{error}\n"),
+        Ok(dirs) if dirs.is_empty() => text += "nothing to delete\n",
+        Ok(dirs) => {
+            let mut total = 0;
+            for id in dirs {
+                total += fs.size(id);
+                text += &format!("  rm -r {} # {}\n", fs.path(id), fs.size(id));
+            }
+            text += &format!("frees {total}, {} more than needed\n", total - target);
+        }
+    }
+    text
 }
 
 fn part1(commands: &[Command]) -> u128 {
@@ -151,10 +409,10 @@ fn part1(commands: &[Command]) -> u128 {
         .sum()
 }
 
-fn part2(commands: &[Command]) -> Option<u128> {
+fn part2(commands: &[Command], disk: &Disk) -> Option<u128> {
     let directories = build_directories(commands);
     let root = directories.iter().find(|d| d.name == "/").unwrap();
-    let overflow = root.size - (70_000_000 - 30_000_000);
+    let overflow = disk.overflow(root.size);
     directories
         .iter()
         .filter_map(|directory| {
@@ -166,3 +424,186 @@ fn part2(commands: &[Command]) -> Option<u128> {
         })
         .min()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// Transcript of a random tree with `dirs` directories below the root
+    fn random_transcript(rng: &mut fastrand::Rng, dirs: usize) -> String {
+        let mut paths = vec!["/".to_owned()];
+        for i in 0..dirs {
+            let parent = &paths[rng.usize(..paths.len())];
+            paths.push(format!("{}{i}/", parent));
+        }
+        let mut transcript = String::new();
+        for path in &paths {
+            transcript += &format!("$ cd {path}\n$ ls\n");
+            for child in paths.iter().filter(|child| {
+                child
+                    .strip_prefix(path.as_str())
+                    .is_some_and(|rest| !rest.is_empty() && rest.matches('/').count() == 1)
+            }) {
+                transcript += &format!("dir {}\n", &child[path.len()..child.len() - 1]);
+            }
+            for file in 0..rng.usize(..3) {
+                transcript += &format!("{} f{file}\n", rng.u32(1..=1_000));
+            }
+        }
+        transcript
+    }
+
+    /// Files of the tree the generator is checked against, sizes are the file lengths
+    const TREE: [(&str, usize); 9] = [
+        ("a.txt", 100),
+        ("b/c.txt", 20),
+        ("b/d/e.txt", 3),
+        ("b/d/f/g.txt", 4_000),
+        ("b/d/f/h i.txt", 55),
+        ("j-1/k.dat", 6),
+        ("j-1/l/m/n/o.txt", 700),
+        ("p/q.txt", 0),
+        ("r/s/t/.hidden", 8),
+    ];
+
+    /// Directories with no files anywhere below them
+    const EMPTY: [&str; 2] = ["empty", "r/u/v"];
+
+    /// Size of every directory the transcript should hold, worked out from `TREE` alone:
+    /// directories up to one level past `max_depth` appear, files count when their parent
+    /// is at most `max_depth` deep
+    fn expected_sizes(max_depth: Option<usize>) -> BTreeMap<String, u128> {
+        let listed = |depth: usize| max_depth.is_none_or(|max_depth| depth <= max_depth);
+        let mut sizes = BTreeMap::from([("/".to_owned(), 0)]);
+        let dirs = TREE
+            .iter()
+            .map(|(path, _)| path.rsplit_once('/').map_or("", |(dir, _)| dir))
+            .chain(EMPTY);
+        for dir in dirs {
+            let parts = dir
+                .split('/')
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>();
+            for depth in 1..=parts.len() {
+                if listed(depth - 1) {
+                    sizes.insert(format!("/{}", parts[..depth].join("/")), 0);
+                }
+            }
+        }
+        for (path, size) in TREE {
+            let parts = path.split('/').collect::<Vec<_>>();
+            let parent_depth = parts.len() - 1;
+            if !listed(parent_depth) {
+                continue;
+            }
+            *sizes.get_mut("/").unwrap() += size as u128;
+            for depth in 1..=parent_depth {
+                *sizes
+                    .get_mut(&format!("/{}", parts[..depth].join("/")))
+                    .unwrap() += size as u128;
+            }
+        }
+        sizes
+    }
+
+    #[test]
+    fn generated_transcripts_rebuild_directory_sizes() {
+        let root = std::env::temp_dir().join(format!("day-07-generate-{}", std::process::id()));
+        for (path, size) in TREE {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, vec![b'x'; size]).unwrap();
+        }
+        for dir in EMPTY {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        for order in [generate::Order::DepthFirst, generate::Order::BreadthFirst] {
+            for max_depth in [None, Some(0), Some(1), Some(2), Some(3)] {
+                let options = generate::Options { order, max_depth };
+                let transcript = generate::transcript(&root, &options).unwrap();
+                let (_, commands) = parse(&transcript).unwrap();
+                let sizes = build_directories(&commands)
+                    .into_iter()
+                    .map(|directory| (directory.name, directory.size))
+                    .collect::<BTreeMap<_, _>>();
+                assert_eq!(sizes, expected_sizes(max_depth), "{order:?} {max_depth:?}");
+            }
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn human_size_rounds_up_like_du() {
+        for (size, expected) in [
+            (0, "0"),
+            (1023, "1023"),
+            (1024, "1.0K"),
+            (1025, "1.1K"),
+            (10 * 1024 - 1, "10K"),
+            (1_048_575, "1.0M"),
+            (1_048_576, "1.0M"),
+            (1_048_577, "1.1M"),
+            (1024 * 1024 * 1024 - 1, "1.0G"),
+        ] {
+            assert_eq!(human_size(size), expected, "{size}");
+        }
+    }
+
+    #[test]
+    fn cleanup_matches_every_choice_of_directories() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        for _ in 0..300 {
+            let dirs = rng.usize(..10);
+            let transcript = random_transcript(&mut rng, dirs);
+            let (_, commands) = parse(&transcript).unwrap();
+            let fs = FileSystem::from_commands(&commands);
+            let candidates = fs.find(|fs, id| fs.is_dir(id) && id != FileSystem::ROOT);
+            let used = fs.size(FileSystem::ROOT);
+            let disk = Disk {
+                capacity: used + 1_000,
+                required: 1_000 + rng.u128(..=used),
+            };
+            let target = disk.overflow(used);
+
+            let best = (0..1u32 << candidates.len())
+                .map(|mask| {
+                    (0..candidates.len())
+                        .filter(|i| mask & 1 << i != 0)
+                        .map(|i| candidates[i])
+                        .collect::<Vec<_>>()
+                })
+                .filter(|picked| {
+                    picked.iter().all(|&a| {
+                        picked
+                            .iter()
+                            .all(|&b| a == b || !fs.path(b).starts_with(&(fs.path(a) + "/")))
+                    })
+                })
+                .map(|picked| picked.iter().map(|&id| fs.size(id)).sum::<u128>())
+                .filter(|&total| total >= target)
+                .min();
+
+            let planned = cleanup(&fs, &disk);
+            assert_eq!(
+                planned
+                    .as_ref()
+                    .ok()
+                    .map(|dirs| dirs.iter().map(|&id| fs.size(id)).sum::<u128>()),
+                best
+            );
+            if best.is_none() {
+                assert_eq!(planned, Err(CleanupError::Unreachable));
+            }
+            if let Ok(dirs) = planned {
+                for &a in &dirs {
+                    for &b in &dirs {
+                        assert!(a == b || !fs.path(b).starts_with(&(fs.path(a) + "/")));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,41 +1,133 @@
 use nom::{
-    bytes::complete::take,
-    character::complete::line_ending,
-    combinator::map_res,
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{anychar, line_ending, space0, space1, u64 as number},
+    combinator::{all_consuming, map, map_opt, verify},
     multi::{many1, separated_list1},
+    sequence::delimited,
     IResult,
 };
 
 fn main() {
-    let (_, trees) = parse(include_str!("in").trim_end()).unwrap();
-    dbg!(part1(&trees));
-    dbg!(part2(&trees));
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let (heights, input, mode) = match args[..] {
+        ["alphabet", alphabet, path, ref mode @ ..] => (
+            Heights::Alphabet(alphabet.to_owned()),
+            std::fs::read_to_string(path).unwrap(),
+            mode,
+        ),
+        ["numbers", path, ref mode @ ..] => (
+            Heights::Numbers,
+            std::fs::read_to_string(path).unwrap(),
+            mode,
+        ),
+        ref mode => (Heights::default(), include_str!("in").to_owned(), mode),
+    };
+    let (_, trees) = parse(input.trim_end(), &heights).unwrap();
+    match mode {
+        ["bench", size] => bench(size.parse().unwrap()),
+        ["ascii"] => print!(
+            "{}",
+            ascii_overlay(
+                &visibility(&trees),
+                &scenic_scores(&trees, &View::default())
+            )
+        ),
+        [format @ ("pgm" | "ppm"), path, ref scale @ ..] => {
+            let scale = scale.first().map_or(1, |scale| scale.parse().unwrap());
+            let image = image(
+                &visibility(&trees),
+                &scenic_scores(&trees, &View::default()),
+                *format == "ppm",
+                scale,
+            );
+            std::fs::write(path, image).unwrap();
+        }
+        ["top", k, ref options @ ..] => {
+            let scores = scenic_scores(&trees, &view_options(options));
+            for (x, y, score) in top_trees(&scores, k.parse().unwrap()) {
+                println!("({x}, {y}) height {} scores {score}", trees[y][x]);
+            }
+        }
+        ["region", x0, y0, x1, y1, ref options @ ..] => {
+            let scores = scenic_scores(&trees, &view_options(options));
+            let corner = |x: &str, y: &str| (x.parse().unwrap(), y.parse().unwrap());
+            dbg!(best_in_region(&scores, corner(x0, y0), corner(x1, y1)));
+        }
+        _ => {
+            dbg!(part1(&trees));
+            dbg!(part2(&trees));
+        }
+    }
 }
 
-fn tree(input: &str) -> IResult<&str, usize> {
-    map_res(take(1u32), |c: &str| c.parse::<usize>())(input)
+/// Reads `--cap N` and `--block H` after a query
+fn view_options(args: &[&str]) -> View {
+    let mut view = View::default();
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let value = args.next().and_then(|value| value.parse().ok());
+        match arg {
+            "--cap" => view.max_distance = value,
+            "--block" => view.block_height = value.unwrap_or(0),
+            _ => panic!("unknown option {arg}"),
+        }
+    }
+    view
 }
 
-fn parse(input: &str) -> IResult<&str, Vec<Vec<usize>>> {
-    separated_list1(line_ending, many1(tree))(input)
+/// How a row of the map spells tree heights
+#[derive(Debug, Clone)]
+enum Heights {
+    /// One character per tree, its height is the position in the alphabet
+    Alphabet(String),
+    /// Numbers separated by spaces or commas
+    Numbers,
 }
 
-fn part1(trees: &[Vec<usize>]) -> usize {
-    let size = trees.len() - 1;
+impl Default for Heights {
+    fn default() -> Self {
+        Self::Alphabet("0123456789".to_owned())
+    }
+}
+
+fn row<'a>(heights: &'a Heights) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<usize>> {
+    move |input| match heights {
+        Heights::Alphabet(alphabet) => many1(map_opt(anychar, |c| {
+            alphabet.chars().position(|letter| letter == c)
+        }))(input),
+        Heights::Numbers => separated_list1(
+            alt((delimited(space0, tag(","), space0), space1)),
+            map(number, |height| height as usize),
+        )(input),
+    }
+}
+
+fn parse<'a>(input: &'a str, heights: &'a Heights) -> IResult<&'a str, Vec<Vec<usize>>> {
+    all_consuming(verify(
+        separated_list1(line_ending, row(heights)),
+        |rows: &Vec<Vec<usize>>| rows.iter().all(|row| row.len() == rows[0].len()),
+    ))(input)
+}
+
+/// Whether each tree can be seen from outside the forest
+fn visibility(trees: &[Vec<usize>]) -> Vec<Vec<bool>> {
+    let (height, width) = (trees.len(), trees[0].len());
     let mut visible_trees: Vec<Vec<bool>> = trees
         .iter()
         .enumerate()
         .map(|(y, row)| {
             row.iter()
                 .enumerate()
-                .map(|(x, _)| y == 0 || y == size || x == 0 || x == size)
+                .map(|(x, _)| y == 0 || y == height - 1 || x == 0 || x == width - 1)
                 .collect()
         })
         .collect();
 
-    for y in 0..trees.len() {
+    for y in 0..height {
         let mut current = trees[y][0];
-        for x in 1..trees[0].len() {
+        for x in 1..width {
             if trees[y][x] > current {
                 current = trees[y][x];
                 visible_trees[y][x] = true;
@@ -43,9 +135,9 @@ fn part1(trees: &[Vec<usize>]) -> usize {
         }
     }
 
-    for y in (0..trees.len()).rev() {
-        let mut current = trees[y][trees.len() - 1];
-        for x in (0..trees[0].len() - 1).rev() {
+    for y in (0..height).rev() {
+        let mut current = trees[y][width - 1];
+        for x in (0..width - 1).rev() {
             if trees[y][x] > current {
                 current = trees[y][x];
                 visible_trees[y][x] = true;
@@ -53,9 +145,9 @@ fn part1(trees: &[Vec<usize>]) -> usize {
         }
     }
 
-    for x in 0..trees.len() {
+    for x in 0..width {
         let mut current = trees[0][x];
-        for y in 1..trees[0].len() {
+        for y in 1..height {
             if trees[y][x] > current {
                 current = trees[y][x];
                 visible_trees[y][x] = true;
@@ -63,9 +155,9 @@ fn part1(trees: &[Vec<usize>]) -> usize {
         }
     }
 
-    for x in (0..trees.len()).rev() {
-        let mut current = trees[trees.len() - 1][x];
-        for y in (0..trees[0].len() - 1).rev() {
+    for x in (0..width).rev() {
+        let mut current = trees[height - 1][x];
+        for y in (0..height - 1).rev() {
             if trees[y][x] > current {
                 current = trees[y][x];
                 visible_trees[y][x] = true;
@@ -73,30 +165,260 @@ fn part1(trees: &[Vec<usize>]) -> usize {
         }
     }
 
-    visible_trees.iter().flatten().filter(|&&v| v).count()
+    visible_trees
+}
+
+fn part1(trees: &[Vec<usize>]) -> usize {
+    visibility(trees).iter().flatten().filter(|&&v| v).count()
 }
 
 fn part2(trees: &[Vec<usize>]) -> usize {
-    let size = trees.len();
+    scenic_scores(trees, &View::default())
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap()
+}
+
+/// Limits on how far trees see when scoring them
+#[derive(Debug, Clone, Copy, Default)]
+struct View {
+    /// Views longer than this are cut short
+    max_distance: Option<usize>,
+    /// Trees below this height never block a view
+    block_height: usize,
+}
+
+/// Number of trees each tree sees over while walking `line` in `order`.
+/// Keeps a stack of the trees that can still block, at least `block_height` tall and strictly
+/// shorter towards the top, so every tree is pushed and popped at most once. A tree below the
+/// threshold is stopped by the top of the stack, a taller one pops what it sees over first
+fn viewing_distances(
+    line: &[usize],
+    order: impl Iterator<Item = usize>,
+    view: &View,
+) -> Vec<usize> {
+    let mut blockers = Vec::<(usize, usize)>::new();
+    let mut distances = vec![0; line.len()];
+    for (step, i) in order.enumerate() {
+        let tree = line[i];
+        let blocking = tree >= view.block_height;
+        if blocking {
+            while blockers.last().is_some_and(|&(_, height)| height < tree) {
+                blockers.pop();
+            }
+        }
+        let distance = blockers.last().map_or(step, |&(j, _)| step - j - 1);
+        distances[i] = view.max_distance.map_or(distance, |cap| distance.min(cap));
+        if blocking {
+            if blockers.last().is_some_and(|&(_, height)| height == tree) {
+                blockers.pop();
+            }
+            blockers.push((step, tree));
+        }
+    }
+    distances
+}
+
+/// Product of the viewing distances in all four directions for every tree
+fn scenic_scores(trees: &[Vec<usize>], view: &View) -> Vec<Vec<usize>> {
+    let width = trees[0].len();
+    let both_ways = |line: &[usize]| {
+        let back = viewing_distances(line, 0..line.len(), view);
+        let forward = viewing_distances(line, (0..line.len()).rev(), view);
+        back.into_iter()
+            .zip(forward)
+            .map(|(a, b)| a * b)
+            .collect::<Vec<_>>()
+    };
+    let mut scores = trees.iter().map(|row| both_ways(row)).collect::<Vec<_>>();
+    for x in 0..width {
+        let column = trees.iter().map(|row| row[x]).collect::<Vec<_>>();
+        for (row, score) in scores.iter_mut().zip(both_ways(&column)) {
+            row[x] *= score;
+        }
+    }
+    scores
+}
+
+/// Scans every direction from every tree, kept as the reference for `scenic_scores`
+fn scenic_scores_naive(trees: &[Vec<usize>], view: &View) -> Vec<Vec<usize>> {
+    let (height, width) = (trees.len(), trees[0].len());
+    let cap = view.max_distance.unwrap_or(usize::MAX);
     trees
         .iter()
         .enumerate()
-        .flat_map(move |(y, column)| {
-            column.iter().enumerate().map(move |(x, &height)| {
-                [
-                    (0..x).rev().take_while(|&xx| trees[y][xx] < height).count(),
-                    ((x + 1)..size)
-                        .take_while(|&xx| trees[y][xx] < height)
-                        .count(),
-                    (0..y).rev().take_while(|&yy| trees[yy][x] < height).count(),
-                    ((y + 1)..size)
-                        .take_while(|&yy| trees[yy][x] < height)
-                        .count(),
-                ]
+        .map(|(y, column)| {
+            column
                 .iter()
-                .product()
-            })
+                .enumerate()
+                .map(|(x, &tree)| {
+                    let blocking = tree.max(view.block_height);
+                    [
+                        (0..x)
+                            .rev()
+                            .take_while(|&xx| trees[y][xx] < blocking)
+                            .take(cap)
+                            .count(),
+                        ((x + 1)..width)
+                            .take_while(|&xx| trees[y][xx] < blocking)
+                            .take(cap)
+                            .count(),
+                        (0..y)
+                            .rev()
+                            .take_while(|&yy| trees[yy][x] < blocking)
+                            .take(cap)
+                            .count(),
+                        ((y + 1)..height)
+                            .take_while(|&yy| trees[yy][x] < blocking)
+                            .take(cap)
+                            .count(),
+                    ]
+                    .iter()
+                    .product()
+                })
+                .collect()
         })
-        .max()
-        .unwrap()
+        .collect()
+}
+
+/// Trees with the highest scores as `(x, y, score)`, best first
+fn top_trees(scores: &[Vec<usize>], k: usize) -> Vec<(usize, usize, usize)> {
+    let mut trees = scores
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, &score)| (x, y, score)))
+        .collect::<Vec<_>>();
+    trees.sort_by(|a, b| b.2.cmp(&a.2).then((a.1, a.0).cmp(&(b.1, b.0))));
+    trees.truncate(k);
+    trees
+}
+
+/// Highest scoring tree inside the rectangle between two corners, both inclusive
+fn best_in_region(
+    scores: &[Vec<usize>],
+    (x0, y0): (usize, usize),
+    (x1, y1): (usize, usize),
+) -> Option<(usize, usize, usize)> {
+    scores
+        .iter()
+        .enumerate()
+        .take(y1 + 1)
+        .skip(y0)
+        .flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .take(x1 + 1)
+                .skip(x0)
+                .map(move |(x, &score)| (x, y, score))
+        })
+        .min_by(|a, b| b.2.cmp(&a.2).then((a.1, a.0).cmp(&(b.1, b.0))))
+}
+
+/// Pseudo-random forest from a seeded generator, so runs are reproducible
+fn generate_forest(width: usize, height: usize, max_height: usize, seed: u64) -> Vec<Vec<usize>> {
+    let mut rng = fastrand::Rng::with_seed(seed);
+    (0..height)
+        .map(|_| (0..width).map(|_| rng.usize(..=max_height)).collect())
+        .collect()
+}
+
+fn bench(size: usize) {
+    let ramp = (0..size)
+        .map(|y| (0..size).map(|x| x + y).collect())
+        .collect::<Vec<Vec<usize>>>();
+    let forests = [9, size].map(|max_height| {
+        (
+            format!("heights up to {max_height}"),
+            generate_forest(size, size, max_height, 42),
+        )
+    });
+    for (name, trees) in forests.into_iter().chain([("ramp".to_owned(), ramp)]) {
+        let start = std::time::Instant::now();
+        let fast = scenic_scores(&trees, &View::default());
+        let fast_time = start.elapsed();
+        let start = std::time::Instant::now();
+        let naive = scenic_scores_naive(&trees, &View::default());
+        let naive_time = start.elapsed();
+        assert_eq!(fast, naive);
+        println!("{size}x{size}, {name}: monotonic stack {fast_time:?}, naive {naive_time:?}");
+    }
+}
+
+/// Score relative to the best one on a square root curve, so low scores stay distinguishable
+fn brightness(score: usize, best: usize) -> f64 {
+    if best == 0 {
+        0.0
+    } else {
+        (score as f64 / best as f64).sqrt()
+    }
+}
+
+/// One letter per tree, `A`..`J` by scenic score brightness, uppercase when visible from outside
+fn ascii_overlay(visible: &[Vec<bool>], scores: &[Vec<usize>]) -> String {
+    let best = scores.iter().flatten().copied().max().unwrap_or(0);
+    let mut overlay = String::new();
+    for (visible, scores) in visible.iter().zip(scores) {
+        for (&visible, &score) in visible.iter().zip(scores) {
+            let decile = (brightness(score, best) * 9.0).round() as u8;
+            let letter = (b'a' + decile) as char;
+            overlay.push(if visible {
+                letter.to_ascii_uppercase()
+            } else {
+                letter
+            });
+        }
+        overlay.push('\n');
+    }
+    overlay
+}
+
+/// Binary PGM of scenic scores, or PPM with visible trees tinted green, `scale` pixels per tree
+fn image(visible: &[Vec<bool>], scores: &[Vec<usize>], colour: bool, scale: usize) -> Vec<u8> {
+    let best = scores.iter().flatten().copied().max().unwrap_or(0);
+    let (height, width) = (scores.len() * scale, scores[0].len() * scale);
+    let magic = if colour { "P6" } else { "P5" };
+    let mut image = format!("{magic}\n{width} {height}\n255\n").into_bytes();
+    for (visible, scores) in visible.iter().zip(scores) {
+        let mut line = Vec::with_capacity(width * 3);
+        for (&visible, &score) in visible.iter().zip(scores) {
+            let value = (brightness(score, best) * 255.0).round() as u8;
+            let pixel = match (colour, visible) {
+                (false, _) => vec![value],
+                (true, false) => vec![value; 3],
+                (true, true) => vec![value / 2, value.max(64), value / 2],
+            };
+            for _ in 0..scale {
+                line.extend(&pixel);
+            }
+        }
+        for _ in 0..scale {
+            image.extend(&line);
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compares both scenic score versions on random forests of random shapes
+    #[test]
+    fn scenic_scores_match_the_naive_scan() {
+        let mut rng = fastrand::Rng::with_seed(8);
+        for seed in 1..=2_000 {
+            let trees =
+                generate_forest(rng.usize(1..=41), rng.usize(1..=41), rng.usize(..12), seed);
+            let view = View {
+                max_distance: rng.bool().then(|| rng.usize(..8)),
+                block_height: rng.usize(..6),
+            };
+            assert_eq!(
+                scenic_scores(&trees, &view),
+                scenic_scores_naive(&trees, &view),
+                "seed {seed} disagrees with {view:?}"
+            );
+        }
+    }
 }
@@ -15,8 +15,40 @@ use num::{Complex, Signed};
 
 fn main() {
     let (_, moves) = parse(include_str!("in").trim_end()).unwrap();
-    dbg!(part1(&moves));
-    dbg!(part2(&moves));
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["rope", length, ref rule @ ..] => {
+            let follow = follow(rule).expect("unknown follow rule");
+            let trajectory = walk(&moves, length.parse().unwrap(), follow.as_ref());
+            println!("{} steps", trajectory.history.len() - 1);
+            for knot in 0..trajectory.knots() {
+                println!(
+                    "knot {knot}: visited {} cells, first moved at step {:?}",
+                    trajectory.visited(knot).len(),
+                    trajectory.first_move(knot)
+                );
+            }
+        }
+        ["frames", length, ref rule @ ..] => {
+            let follow = follow(rule).expect("unknown follow rule");
+            let trajectory = walk(&moves, length.parse().unwrap(), follow.as_ref());
+            let mut step = 0;
+            for &(direction, size) in &moves {
+                step += size as usize;
+                println!("== {} {size} ==\n", direction.letter());
+                println!("{}", trajectory.render(step));
+            }
+        }
+        ["svg", length, path, ref rule @ ..] => {
+            let follow = follow(rule).expect("unknown follow rule");
+            let trajectory = walk(&moves, length.parse().unwrap(), follow.as_ref());
+            std::fs::write(path, trajectory.to_svg()).unwrap();
+        }
+        _ => {
+            dbg!(part1(&moves));
+            dbg!(part2(&moves));
+        }
+    }
 }
 
 trait Chebyshev<T>
@@ -38,14 +70,46 @@ enum Direction {
     Right,
     Down,
     Left,
+    UpRight,
+    DownRight,
+    DownLeft,
+    UpLeft,
 }
 impl Direction {
+    const ALL: [Direction; 8] = [
+        Self::Up,
+        Self::Right,
+        Self::Down,
+        Self::Left,
+        Self::UpRight,
+        Self::DownRight,
+        Self::DownLeft,
+        Self::UpLeft,
+    ];
+
+    fn letter(&self) -> &'static str {
+        match self {
+            Self::Up => "U",
+            Self::Right => "R",
+            Self::Down => "D",
+            Self::Left => "L",
+            Self::UpRight => "UR",
+            Self::DownRight => "DR",
+            Self::DownLeft => "DL",
+            Self::UpLeft => "UL",
+        }
+    }
+
     fn offset(&self) -> Point {
         match self {
             Self::Up => Complex::new(0, 1),
             Self::Right => Complex::new(1, 0),
             Self::Down => Complex::new(0, -1),
             Self::Left => Complex::new(-1, 0),
+            Self::UpRight => Complex::new(1, 1),
+            Self::DownRight => Complex::new(1, -1),
+            Self::DownLeft => Complex::new(-1, -1),
+            Self::UpLeft => Complex::new(-1, 1),
         }
     }
 }
@@ -58,42 +122,192 @@ fn parse(input: &str) -> IResult<&str, Vec<Move>> {
         line_ending,
         map_opt(
             separated_pair(alpha1, tag(" "), character::complete::u32),
-            |(letter, size)| match letter {
-                "U" => Some((Direction::Up, size)),
-                "R" => Some((Direction::Right, size)),
-                "D" => Some((Direction::Down, size)),
-                "L" => Some((Direction::Left, size)),
-                _ => None,
+            |(letter, size)| {
+                let direction = Direction::ALL
+                    .into_iter()
+                    .find(|direction| direction.letter() == letter)?;
+                Some((direction, size))
             },
         ),
     ))(input)
 }
 
-fn walk<const LENGTH: usize>(moves: &[Move]) -> usize {
-    let mut knots = [Complex::new(0, 0); LENGTH];
-    let mut visited = HashSet::new();
-    visited.insert(knots[LENGTH - 1]);
+/// Positions of every knot after every single step, index 0 is the starting state
+#[derive(Debug, Clone)]
+struct Trajectory {
+    history: Vec<Vec<Point>>,
+}
+
+impl Trajectory {
+    fn knots(&self) -> usize {
+        self.history[0].len()
+    }
+
+    fn positions(&self, knot: usize) -> impl Iterator<Item = Point> + '_ {
+        self.history.iter().map(move |knots| knots[knot])
+    }
+
+    fn visited(&self, knot: usize) -> HashSet<Point> {
+        self.positions(knot).collect()
+    }
+
+    /// Step after which the knot first left its starting position
+    fn first_move(&self, knot: usize) -> Option<usize> {
+        let start = self.history[0][knot];
+        self.positions(knot).position(|position| position != start)
+    }
+
+    /// Corners of the box holding every position any knot ever takes
+    fn viewport(&self) -> (Point, Point) {
+        let all = self.history.iter().flatten();
+        let min = all.clone().fold(Complex::new(0, 0), |min, p| {
+            Complex::new(min.re.min(p.re), min.im.min(p.im))
+        });
+        let max = all.fold(Complex::new(0, 0), |max, p| {
+            Complex::new(max.re.max(p.re), max.im.max(p.im))
+        });
+        (min, max)
+    }
+
+    fn label(&self, knot: usize) -> char {
+        match knot {
+            0 => 'H',
+            knot if knot == self.knots() - 1 => 'T',
+            knot => char::from_digit(knot as u32 % 10, 10).unwrap(),
+        }
+    }
+
+    /// Draws the rope after `step` like the puzzle does, front knots cover the ones behind
+    fn render(&self, step: usize) -> String {
+        let (min, max) = self.viewport();
+        let knots = &self.history[step];
+        let mut frame = String::new();
+        for im in (min.im..=max.im).rev() {
+            for re in min.re..=max.re {
+                let cell = Complex::new(re, im);
+                frame.push(match knots.iter().position(|&knot| knot == cell) {
+                    Some(knot) => self.label(knot),
+                    None if cell == self.history[0][0] => 's',
+                    None => '.',
+                });
+            }
+            frame.push('\n');
+        }
+        frame
+    }
+
+    /// Path of every knot as a polyline, hue going from red at the head to violet at the tail
+    fn to_svg(&self) -> String {
+        const CELL: i32 = 10;
+        let (min, max) = self.viewport();
+        let (width, height) = ((max.re - min.re + 1) * CELL, (max.im - min.im + 1) * CELL);
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+        );
+        svg += "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n";
+        for knot in (0..self.knots()).rev() {
+            let points = self
+                .positions(knot)
+                .map(|p| {
+                    let x = (p.re - min.re) * CELL + CELL / 2;
+                    let y = (max.im - p.im) * CELL + CELL / 2;
+                    format!("{x},{y}")
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let hue = 270 * knot / (self.knots() - 1).max(1);
+            svg += &format!(
+                "<polyline fill=\"none\" stroke=\"hsl({hue}, 80%, 45%)\" stroke-width=\"2\" stroke-opacity=\"0.7\" points=\"{points}\"><title>knot {}</title></polyline>\n",
+                self.label(knot)
+            );
+        }
+        svg += "</svg>\n";
+        svg
+    }
+}
+
+/// How a knot reacts to the knot ahead of it
+trait Follow {
+    /// Offset the knot moves by when the knot ahead is `delta` away from it
+    fn step(&self, delta: Point) -> Point;
+}
+
+/// The puzzle rope: once the knot ahead is no longer touching, step straight or diagonally towards it
+struct Taut;
+
+impl Follow for Taut {
+    fn step(&self, delta: Point) -> Point {
+        Lagging { slack: 1 }.step(delta)
+    }
+}
+
+/// Like `Taut`, but the knot ahead may get `slack` cells away before it pulls
+struct Lagging {
+    slack: i32,
+}
+
+impl Follow for Lagging {
+    fn step(&self, delta: Point) -> Point {
+        if delta.max_norm() > self.slack {
+            Complex::new(delta.re.signum(), delta.im.signum())
+        } else {
+            Complex::new(0, 0)
+        }
+    }
+}
+
+/// Stretches until the knot ahead is more than `reach` steps away in Manhattan distance,
+/// then moves one step along the longer axis, never diagonally
+struct Elastic {
+    reach: i32,
+}
+
+impl Follow for Elastic {
+    fn step(&self, delta: Point) -> Point {
+        if delta.l1_norm() <= self.reach {
+            Complex::new(0, 0)
+        } else if delta.re.abs() >= delta.im.abs() {
+            Complex::new(delta.re.signum(), 0)
+        } else {
+            Complex::new(0, delta.im.signum())
+        }
+    }
+}
+
+fn follow(rule: &[&str]) -> Option<Box<dyn Follow>> {
+    match rule {
+        [] | ["taut"] => Some(Box::new(Taut)),
+        ["lagging", slack] => Some(Box::new(Lagging {
+            slack: slack.parse().ok()?,
+        })),
+        ["elastic", reach] => Some(Box::new(Elastic {
+            reach: reach.parse().ok()?,
+        })),
+        _ => None,
+    }
+}
+
+fn walk(moves: &[Move], length: usize, follow: &dyn Follow) -> Trajectory {
+    assert!(length > 0, "a rope needs at least one knot");
+    let mut knots = vec![Complex::new(0, 0); length];
+    let mut history = vec![knots.clone()];
     for &(direction, size) in moves {
         for _ in 0..size {
             knots[0] += direction.offset();
-            for i in 1..LENGTH {
-                let current = knots[i];
-                let previous = knots[i - 1];
-                let delta = previous - current;
-                if delta.max_norm() > 1 {
-                    knots[i] += Complex::new(delta.re.signum(), delta.im.signum());
-                }
+            for i in 1..length {
+                let delta = knots[i - 1] - knots[i];
+                knots[i] += follow.step(delta);
             }
-            visited.insert(knots[LENGTH - 1]);
+            history.push(knots.clone());
         }
     }
-    visited.len()
+    Trajectory { history }
 }
 
 fn part1(moves: &[Move]) -> usize {
-    walk::<2>(moves)
+    walk(moves, 2, &Taut).visited(1).len()
 }
 
 fn part2(moves: &[Move]) -> usize {
-    walk::<10>(moves)
+    walk(moves, 10, &Taut).visited(9).len()
 }
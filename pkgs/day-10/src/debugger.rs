@@ -0,0 +1,280 @@
+use std::{
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use crate::{Cpu, CycleTable, Instruction, Register, Registers, REGISTERS, WIDTH};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Breakpoint {
+    Cycle(usize),
+    Condition(Register, Comparison, i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    const ALL: [(Comparison, &'static str); 6] = [
+        (Self::Less, "<"),
+        (Self::LessOrEqual, "<="),
+        (Self::Equal, "=="),
+        (Self::NotEqual, "!="),
+        (Self::GreaterOrEqual, ">="),
+        (Self::Greater, ">"),
+    ];
+
+    fn holds(&self, left: i64, right: i64) -> bool {
+        match self {
+            Self::Less => left < right,
+            Self::LessOrEqual => left <= right,
+            Self::Equal => left == right,
+            Self::NotEqual => left != right,
+            Self::GreaterOrEqual => left >= right,
+            Self::Greater => left > right,
+        }
+    }
+}
+
+impl Breakpoint {
+    /// Reads `cycle 20` or a register condition like `x > 20`
+    pub fn parse(words: &[&str]) -> Option<Self> {
+        match *words {
+            ["cycle", cycle] => Some(Self::Cycle(cycle.parse().ok()?)),
+            [register, comparison, value] => {
+                let register = REGISTERS
+                    .iter()
+                    .position(|name| register.eq_ignore_ascii_case(&name.to_string()))?;
+                let (comparison, _) = Comparison::ALL
+                    .into_iter()
+                    .find(|&(_, symbol)| symbol == comparison)?;
+                Some(Self::Condition(register, comparison, value.parse().ok()?))
+            }
+            _ => None,
+        }
+    }
+
+    fn hit(&self, cycle: usize, registers: &Registers) -> bool {
+        match *self {
+            Self::Cycle(at) => cycle == at,
+            Self::Condition(register, comparison, value) => {
+                comparison.holds(registers[register], value)
+            }
+        }
+    }
+
+    /// Whether the breakpoint fires on `trace`, a condition only does when it was false the
+    /// cycle before, so `continue` runs on until it turns true again
+    fn triggered(&self, previous: Option<&Trace>, trace: &Trace) -> bool {
+        self.hit(trace.cycle, &trace.registers)
+            && !previous.is_some_and(|previous| self.hit(previous.cycle, &previous.registers))
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Cycle(cycle) => write!(f, "cycle {cycle}"),
+            Self::Condition(register, comparison, value) => {
+                let (_, symbol) = Comparison::ALL
+                    .into_iter()
+                    .find(|&(candidate, _)| candidate == comparison)
+                    .unwrap();
+                write!(f, "{} {symbol} {value}", REGISTERS[register])
+            }
+        }
+    }
+}
+
+/// What happened during one cycle
+#[derive(Debug, Clone)]
+pub struct Trace {
+    cycle: usize,
+    pc: usize,
+    instruction: Instruction,
+    registers: Registers,
+}
+
+impl Trace {
+    /// Column and row of the CRT pixel drawn during this cycle
+    fn pixel(&self) -> (usize, usize) {
+        ((self.cycle - 1) % WIDTH, (self.cycle - 1) / WIDTH)
+    }
+
+    fn lit(&self) -> bool {
+        (self.pixel().0 as i64).abs_diff(self.registers[0]) <= 1
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Wider than a register so the sprite around `i64::MAX` still prints
+        let x = self.registers[0] as i128;
+        let (column, row) = self.pixel();
+        write!(
+            f,
+            "cycle {:>4} pc {:>3} {:<10} x={x:<4} sprite {}..={} pixel ({column:>2}, {row}) {}",
+            self.cycle,
+            self.pc,
+            self.instruction.to_string(),
+            x - 1,
+            x + 1,
+            if self.lit() { '#' } else { '.' }
+        )?;
+        for (name, value) in REGISTERS.iter().zip(self.registers).skip(1) {
+            write!(f, " {name}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Debugger<'a> {
+    program: &'a [Instruction],
+    table: &'a CycleTable,
+    cpu: Cpu,
+    breakpoints: Vec<Breakpoint>,
+    trace: Vec<Trace>,
+}
+
+impl<'a> Debugger<'a> {
+    /// Longest `continue` before giving up on a program that never ends
+    const MAX_CYCLES: usize = 1_000_000;
+
+    pub fn new(program: &'a [Instruction], table: &'a CycleTable) -> Self {
+        Self {
+            program,
+            table,
+            cpu: Cpu::new(),
+            breakpoints: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    /// Runs a single cycle and records it, or nothing once the program ended
+    fn step(&mut self) -> Option<&Trace> {
+        let pc = self.cpu.pc;
+        let instruction = *self.program.get(pc)?;
+        let registers = self.cpu.tick(self.program, self.table)?;
+        self.trace.push(Trace {
+            cycle: self.trace.len() + 1,
+            pc,
+            instruction,
+            registers,
+        });
+        self.trace.last()
+    }
+
+    /// Runs up to `cycles` cycles, stopping early at a breakpoint when `stop_at_breakpoints` is set
+    fn run(
+        &mut self,
+        cycles: usize,
+        stop_at_breakpoints: bool,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        for _ in 0..cycles {
+            let Some(trace) = self.step() else {
+                return writeln!(out, "program ended after {} cycles", self.trace.len());
+            };
+            let trace = trace.clone();
+            if !stop_at_breakpoints {
+                writeln!(out, "{trace}")?;
+                continue;
+            }
+            let previous = self.trace.len().checked_sub(2).map(|i| &self.trace[i]);
+            if let Some(breakpoint) = self
+                .breakpoints
+                .iter()
+                .find(|breakpoint| breakpoint.triggered(previous, &trace))
+            {
+                writeln!(out, "breakpoint {breakpoint}")?;
+                return writeln!(out, "{trace}");
+            }
+        }
+        Ok(())
+    }
+
+    fn dump(&self, path: &str) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        for trace in &self.trace {
+            writeln!(file, "{trace}")?;
+        }
+        file.flush()
+    }
+
+    /// Runs one command, returns false when the session should end
+    pub fn command(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words[..] {
+            [] => {}
+            ["step" | "s"] => self.run(1, false, out)?,
+            ["step" | "s", cycles] => match cycles.parse() {
+                Ok(cycles) => self.run(cycles, false, out)?,
+                Err(_) => writeln!(out, "expected a number of cycles")?,
+            },
+            ["continue" | "c"] => self.run(Self::MAX_CYCLES, true, out)?,
+            ["break" | "b", ref condition @ ..] => match Breakpoint::parse(condition) {
+                Some(breakpoint) => {
+                    writeln!(out, "breakpoint {} at {breakpoint}", self.breakpoints.len())?;
+                    self.breakpoints.push(breakpoint);
+                }
+                None => writeln!(out, "expected `break cycle N` or `break x > N`")?,
+            },
+            ["delete" | "d", index] => match index.parse::<usize>() {
+                Ok(index) if index < self.breakpoints.len() => {
+                    self.breakpoints.remove(index);
+                }
+                _ => writeln!(out, "no breakpoint {index}")?,
+            },
+            ["breakpoints"] => {
+                for (i, breakpoint) in self.breakpoints.iter().enumerate() {
+                    writeln!(out, "{i}: {breakpoint}")?;
+                }
+            }
+            ["registers" | "r"] => {
+                for (name, value) in REGISTERS.iter().zip(self.cpu.registers) {
+                    write!(out, "{name}={value} ")?;
+                }
+                writeln!(out, "pc={} cycle={}", self.cpu.pc, self.trace.len())?;
+            }
+            ["trace", path] => {
+                self.dump(path)?;
+                writeln!(out, "wrote {} cycles to {path}", self.trace.len())?;
+            }
+            ["quit" | "q"] => return Ok(false),
+            _ => writeln!(
+                out,
+                "commands: step [n], continue, break cycle N, break x > N, delete N, breakpoints, registers, trace PATH, quit"
+            )?,
+        }
+        Ok(true)
+    }
+
+    /// Reads commands line by line, prompting when `interactive`
+    pub fn session(&mut self, input: impl BufRead, interactive: bool) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        let mut lines = input.lines();
+        loop {
+            if interactive {
+                write!(out, "(crt) ")?;
+                out.flush()?;
+            }
+            let Some(line) = lines.next() else {
+                return Ok(());
+            };
+            let line = line?;
+            if !interactive {
+                writeln!(out, "(crt) {line}")?;
+            }
+            if !self.command(&line, &mut out)? {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -1,54 +1,285 @@
+use std::collections::HashMap;
+
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::{self, complete::line_ending},
-    combinator::{all_consuming, map},
+    character::{
+        self,
+        complete::{line_ending, one_of},
+    },
+    combinator::{all_consuming, map, value},
     multi::separated_list1,
-    sequence::separated_pair,
+    sequence::{pair, preceded},
     IResult,
 };
 
+mod debugger;
+
+use debugger::Debugger;
+
 fn main() {
     let (_, instructions) = parse(include_str!("in").trim_end()).unwrap();
-    dbg!(part1(&instructions));
-    dbg!(part2(&instructions));
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["run", path, ref cycles @ ..] => {
+            let source = std::fs::read_to_string(path).unwrap();
+            let (_, program) = parse(source.trim_end()).unwrap();
+            let mut table = CycleTable::default();
+            for cost in cycles {
+                let (opcode, cycles) = cost.split_once('=').expect("expected opcode=cycles");
+                table.set(opcode, cycles.parse().unwrap());
+            }
+            let mut cpu = Cpu::new();
+            let cycles = std::iter::from_fn(|| cpu.tick(&program, &table))
+                .take(MAX_CYCLES)
+                .count();
+            if cpu.pc < program.len() {
+                println!("stopped after {cycles} cycles, the program was still running");
+            } else {
+                println!("{cycles} cycles");
+            }
+            for (name, value) in REGISTERS.iter().zip(cpu.registers) {
+                println!("{name} = {value}");
+            }
+        }
+        ["debug", ref args @ ..] => {
+            let (script, program) = debug_options(args);
+            let program = match program {
+                Some(path) => {
+                    let source = std::fs::read_to_string(path).unwrap();
+                    parse(source.trim_end()).unwrap().1
+                }
+                None => instructions.clone(),
+            };
+            let table = CycleTable::default();
+            let mut debugger = Debugger::new(&program, &table);
+            match script {
+                Some(script) => {
+                    let script = std::fs::File::open(script).unwrap();
+                    debugger.session(std::io::BufReader::new(script), false)
+                }
+                None => debugger.session(std::io::stdin().lock(), true),
+            }
+            .unwrap();
+        }
+        ["screen"] => print!("{}", draw(&screen(&simulate(&instructions)))),
+        _ => {
+            dbg!(part1(&instructions));
+            match part2(&instructions) {
+                Ok(letters) => {
+                    dbg!(letters);
+                }
+                Err(unknown) => print!("{unknown}"),
+            }
+        }
+    }
+}
+
+/// Reads `[script] [--program <path>]` after `debug`, without a script the session is
+/// interactive and without a program it debugs the puzzle input
+fn debug_options<'a>(args: &[&'a str]) -> (Option<&'a str>, Option<&'a str>) {
+    let (mut script, mut program) = (None, None);
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        match arg {
+            "--program" => program = Some(*args.next().expect("expected a program path")),
+            _ if script.is_none() => script = Some(arg),
+            _ => panic!("unexpected argument {arg}"),
+        }
+    }
+    (script, program)
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Cycles `run` lets a program take before giving up on it
+const MAX_CYCLES: usize = 100_000;
+
+const REGISTERS: [char; 4] = ['x', 'y', 'z', 'w'];
+
+type Register = usize;
+type Registers = [i64; REGISTERS.len()];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Instruction {
-    Addx(i64),
     Noop,
+    Add(Register, i64),
+    Mul(Register, i64),
+    Set(Register, i64),
+    /// Relative jump
+    Jmp(i64),
+    /// Relative jump taken when the register is not zero
+    Jnz(Register, i64),
 }
 
-fn noop(input: &str) -> IResult<&str, Instruction> {
-    map(tag("noop"), |_| Instruction::Noop)(input)
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Noop => write!(f, "noop"),
+            Self::Jmp(jump) => write!(f, "jmp {jump}"),
+            Self::Add(register, value)
+            | Self::Mul(register, value)
+            | Self::Set(register, value)
+            | Self::Jnz(register, value) => {
+                write!(f, "{}{} {value}", self.opcode(), REGISTERS[register])
+            }
+        }
+    }
+}
+
+impl Instruction {
+    fn opcode(&self) -> &'static str {
+        match self {
+            Self::Noop => "noop",
+            Self::Add(..) => "add",
+            Self::Mul(..) => "mul",
+            Self::Set(..) => "set",
+            Self::Jmp(..) => "jmp",
+            Self::Jnz(..) => "jnz",
+        }
+    }
+}
+
+/// How many cycles each opcode takes to complete
+#[derive(Debug, Clone)]
+struct CycleTable {
+    cycles: HashMap<&'static str, usize>,
+}
+
+impl Default for CycleTable {
+    fn default() -> Self {
+        Self {
+            cycles: HashMap::from([
+                ("noop", 1),
+                ("add", 2),
+                ("mul", 3),
+                ("set", 1),
+                ("jmp", 2),
+                ("jnz", 2),
+            ]),
+        }
+    }
+}
+
+impl CycleTable {
+    fn set(&mut self, opcode: &str, cycles: usize) {
+        let (&opcode, _) = self.cycles.get_key_value(opcode).expect("unknown opcode");
+        self.cycles.insert(opcode, cycles);
+    }
+
+    fn cycles(&self, instruction: &Instruction) -> usize {
+        self.cycles[instruction.opcode()]
+    }
 }
 
-fn addx(input: &str) -> IResult<&str, Instruction> {
+fn register(input: &str) -> IResult<&str, Register> {
+    map(one_of(&REGISTERS[..]), |name| {
+        REGISTERS.iter().position(|&r| r == name).unwrap()
+    })(input)
+}
+
+/// `<opcode><register> <value>`, like `addx 3` or `jnzy -2`
+fn on_register<'a>(
+    opcode: &'static str,
+    instruction: fn(Register, i64) -> Instruction,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Instruction> {
     map(
-        separated_pair(tag("addx"), tag(" "), character::complete::i64),
-        |(_, value)| Instruction::Addx(value),
-    )(input)
+        pair(
+            preceded(tag(opcode), register),
+            preceded(tag(" "), character::complete::i64),
+        ),
+        move |(register, value)| instruction(register, value),
+    )
+}
+
+fn instruction(input: &str) -> IResult<&str, Instruction> {
+    alt((
+        value(Instruction::Noop, tag("noop")),
+        on_register("add", Instruction::Add),
+        on_register("mul", Instruction::Mul),
+        on_register("set", Instruction::Set),
+        on_register("jnz", Instruction::Jnz),
+        map(
+            preceded(tag("jmp "), character::complete::i64),
+            Instruction::Jmp,
+        ),
+    ))(input)
 }
 
 fn parse(input: &str) -> IResult<&str, Vec<Instruction>> {
-    all_consuming(separated_list1(line_ending, alt((noop, addx))))(input)
+    all_consuming(separated_list1(line_ending, instruction))(input)
 }
 
-fn simulate(instructions: &[Instruction]) -> Vec<i64> {
-    let mut x = 1;
-    let mut history = Vec::with_capacity(instructions.len());
-    for instruction in instructions {
-        history.push(x);
+#[derive(Debug, Clone)]
+struct Cpu {
+    registers: Registers,
+    pc: usize,
+    /// Cycles left before the current instruction completes
+    busy: usize,
+}
+
+impl Cpu {
+    fn new() -> Self {
+        let mut registers = [0; REGISTERS.len()];
+        registers[0] = 1;
+        Self {
+            registers,
+            pc: 0,
+            busy: 0,
+        }
+    }
+
+    /// Runs one cycle, returns the registers as they were during it
+    /// or nothing once the program counter left the program
+    fn tick(&mut self, program: &[Instruction], table: &CycleTable) -> Option<Registers> {
+        let instruction = program.get(self.pc)?;
+        if self.busy == 0 {
+            self.busy = table.cycles(instruction).max(1);
+        }
+        let during = self.registers;
+        self.busy -= 1;
+        if self.busy == 0 {
+            self.execute(*instruction);
+        }
+        Some(during)
+    }
+
+    fn execute(&mut self, instruction: Instruction) {
+        let mut offset = 1;
         match instruction {
-            Instruction::Addx(value) => {
-                history.push(x);
-                x += value;
-            }
             Instruction::Noop => {}
+            Instruction::Add(register, value) => {
+                self.registers[register] = self.registers[register].wrapping_add(value)
+            }
+            Instruction::Mul(register, value) => {
+                self.registers[register] = self.registers[register].wrapping_mul(value)
+            }
+            Instruction::Set(register, value) => self.registers[register] = value,
+            Instruction::Jmp(jump) => offset = jump,
+            Instruction::Jnz(register, jump) => {
+                if self.registers[register] != 0 {
+                    offset = jump;
+                }
+            }
         }
+        self.pc = self
+            .pc
+            .checked_add_signed(offset as isize)
+            .unwrap_or(usize::MAX);
     }
-    history
+}
+
+/// Registers during every cycle until the program ends or `max_cycles` pass
+fn run(program: &[Instruction], table: &CycleTable, max_cycles: usize) -> Vec<Registers> {
+    let mut cpu = Cpu::new();
+    std::iter::from_fn(|| cpu.tick(program, table))
+        .take(max_cycles)
+        .collect()
+}
+
+fn simulate(instructions: &[Instruction]) -> Vec<i64> {
+    run(instructions, &CycleTable::default(), usize::MAX)
+        .into_iter()
+        .map(|registers| registers[0])
+        .collect()
 }
 
 fn part1(instructions: &[Instruction]) -> i64 {
@@ -67,21 +298,89 @@ fn part1(instructions: &[Instruction]) -> i64 {
         .sum()
 }
 
-fn part2(instructions: &[Instruction]) {
-    let history = simulate(instructions);
-    for line in history.chunks(40) {
-        println!(
-            "{}",
+const WIDTH: usize = 40;
+const HEIGHT: usize = 6;
+
+/// Lit pixels of the CRT, the sprite is three pixels wide and centered on X
+fn screen(history: &[i64]) -> Vec<Vec<bool>> {
+    history
+        .chunks(WIDTH)
+        .take(HEIGHT)
+        .map(|line| {
             line.iter()
                 .enumerate()
-                .map(|(i, x)| {
-                    if (i as i64 % 40 - x).abs() <= 1 {
-                        '#'
-                    } else {
-                        '.'
-                    }
-                })
+                .map(|(i, &x)| (i as i64).abs_diff(x) <= 1)
+                .collect()
+        })
+        .collect()
+}
+
+fn draw(screen: &[Vec<bool>]) -> String {
+    screen
+        .iter()
+        .map(|line| {
+            line.iter()
+                .map(|&lit| if lit { '#' } else { '.' })
                 .collect::<String>()
-        );
+                + "\n"
+        })
+        .collect()
+}
+
+/// The 4x6 puzzle font, rows joined top to bottom
+const FONT: [(char, &str); 16] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+#[derive(Debug, Clone)]
+struct UnknownGlyph {
+    position: usize,
+    bitmap: String,
+}
+
+impl std::fmt::Display for UnknownGlyph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "unknown glyph at letter {}:", self.position)?;
+        for row in self.bitmap.as_bytes().chunks(4) {
+            writeln!(f, "{}", String::from_utf8_lossy(row))?;
+        }
+        Ok(())
     }
 }
+
+/// Reads the letters off the screen, each one is 4 pixels wide followed by a blank column.
+/// Pixels past the end of a short program count as dark, so its letters come out unknown
+fn recognise(screen: &[Vec<bool>]) -> Result<String, UnknownGlyph> {
+    let lit = |x: usize, y: usize| screen.get(y).and_then(|line| line.get(x)) == Some(&true);
+    (0..WIDTH / 5)
+        .map(|position| {
+            let bitmap = (0..HEIGHT)
+                .flat_map(|y| (position * 5..position * 5 + 4).map(move |x| (x, y)))
+                .map(|(x, y)| if lit(x, y) { '#' } else { '.' })
+                .collect::<String>();
+            FONT.iter()
+                .find(|(_, glyph)| *glyph == bitmap)
+                .map(|&(letter, _)| letter)
+                .ok_or(UnknownGlyph { position, bitmap })
+        })
+        .collect()
+}
+
+fn part2(instructions: &[Instruction]) -> Result<String, UnknownGlyph> {
+    recognise(&screen(&simulate(instructions)))
+}
@@ -0,0 +1,323 @@
+use std::{fmt, ops::RangeInclusive};
+
+/// Set of integers kept as sorted, disjoint and non-adjacent inclusive ranges
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    ranges: Vec<(i64, i64)>,
+}
+
+impl IntervalSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn normalize(mut ranges: Vec<(i64, i64)>) -> Self {
+        ranges.retain(|(start, end)| start <= end);
+        ranges.sort_unstable();
+        let mut merged: Vec<(i64, i64)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        Self { ranges: merged }
+    }
+
+    pub fn insert(&mut self, range: RangeInclusive<i64>) {
+        let mut ranges = std::mem::take(&mut self.ranges);
+        ranges.push(range.into_inner());
+        *self = Self::normalize(ranges);
+    }
+
+    pub fn ranges(&self) -> impl Iterator<Item = RangeInclusive<i64>> + '_ {
+        self.ranges.iter().map(|&(start, end)| start..=end)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Number of integers in the set, saturating at `u64::MAX` for the whole `i64` line
+    pub fn len(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|&(start, end)| start.abs_diff(end).saturating_add(1))
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Smallest single range containing the whole set
+    pub fn hull(&self) -> Option<RangeInclusive<i64>> {
+        Some(self.ranges.first()?.0..=self.ranges.last()?.1)
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        self.ranges
+            .binary_search_by(|&(start, end)| {
+                if end < value {
+                    std::cmp::Ordering::Less
+                } else if start > value {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.difference(self).is_empty()
+    }
+
+    pub fn overlaps(&self, other: &Self) -> bool {
+        !self.intersection(other).is_empty()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::normalize([&self.ranges[..], &other.ranges[..]].concat())
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a_start, a_end) = self.ranges[i];
+            let (b_start, b_end) = other.ranges[j];
+            let start = a_start.max(b_start);
+            let end = a_end.min(b_end);
+            if start <= end {
+                ranges.push((start, end));
+            }
+            if a_end < b_end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Self { ranges }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let mut j = 0;
+        for &(start, end) in &self.ranges {
+            while j < other.ranges.len() && other.ranges[j].1 < start {
+                j += 1;
+            }
+            let mut cursor = Some(start);
+            for &(cut_start, cut_end) in other.ranges[j..]
+                .iter()
+                .take_while(|&&(cut_start, _)| cut_start <= end)
+            {
+                let Some(from) = cursor else { break };
+                if cut_start > from {
+                    ranges.push((from, cut_start - 1));
+                }
+                cursor = if cut_end >= end {
+                    None
+                } else {
+                    Some(cut_end + 1)
+                };
+            }
+            if let Some(from) = cursor {
+                ranges.push((from, end));
+            }
+        }
+        Self { ranges }
+    }
+
+    /// Sweep-line events, +1 where a range opens and -1 one past where it closes, sorted.
+    /// Positions are `i128` so a range ending at `i64::MAX` still has a place to close
+    fn depth_events<'a>(sets: impl IntoIterator<Item = &'a Self>) -> Vec<(i128, i64)> {
+        let mut events = sets
+            .into_iter()
+            .flat_map(|set| set.ranges.iter())
+            .flat_map(|&(start, end)| [(start as i128, 1), (end as i128 + 1, -1)])
+            .collect::<Vec<_>>();
+        events.sort_unstable();
+        events
+    }
+
+    /// Largest number of the given sets covering a single integer
+    pub fn max_depth<'a>(sets: impl IntoIterator<Item = &'a Self>) -> usize {
+        Self::depth_events(sets)
+            .into_iter()
+            .scan(0, |depth, (_, delta)| {
+                *depth += delta;
+                Some(*depth)
+            })
+            .max()
+            .unwrap_or(0) as usize
+    }
+
+    /// Integers covered by at least `depth` of the given sets, every integer when `depth` is 0
+    pub fn covered_at_least<'a>(sets: impl IntoIterator<Item = &'a Self>, depth: usize) -> Self {
+        if depth == 0 {
+            return Self::from(i64::MIN..=i64::MAX);
+        }
+        let events = Self::depth_events(sets);
+
+        let mut ranges = Vec::new();
+        let mut current = 0;
+        let mut opened = None;
+        for (position, delta) in events {
+            current += delta;
+            match opened {
+                None if current >= depth as i64 => opened = Some(position as i64),
+                Some(start) if current < depth as i64 => {
+                    ranges.push((start, (position - 1) as i64));
+                    opened = None;
+                }
+                _ => {}
+            }
+        }
+        Self::normalize(ranges)
+    }
+}
+
+impl From<RangeInclusive<i64>> for IntervalSet {
+    fn from(range: RangeInclusive<i64>) -> Self {
+        Self::normalize(vec![range.into_inner()])
+    }
+}
+
+impl FromIterator<RangeInclusive<i64>> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item = RangeInclusive<i64>>>(iter: T) -> Self {
+        Self::normalize(iter.into_iter().map(RangeInclusive::into_inner).collect())
+    }
+}
+
+impl fmt::Display for IntervalSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges = self
+            .ranges
+            .iter()
+            .map(|(start, end)| format!("{start}-{end}"))
+            .collect::<Vec<_>>();
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn random_set(rng: &mut fastrand::Rng) -> (IntervalSet, BTreeSet<i64>) {
+        let mut set = IntervalSet::new();
+        let mut oracle = BTreeSet::new();
+        for _ in 0..rng.usize(..5) {
+            let start = rng.i64(-20..20);
+            let end = start + rng.i64(-1..7);
+            set.insert(start..=end);
+            oracle.extend(start..=end);
+        }
+        (set, oracle)
+    }
+
+    fn members(set: &IntervalSet) -> BTreeSet<i64> {
+        set.ranges().flatten().collect()
+    }
+
+    #[test]
+    fn matches_a_plain_set() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        for _ in 0..2_000 {
+            let (a, a_oracle) = random_set(&mut rng);
+            let (b, b_oracle) = random_set(&mut rng);
+
+            assert_eq!(members(&a), a_oracle);
+            assert_eq!(a.len(), a_oracle.len() as u64);
+            assert_eq!(a.is_empty(), a_oracle.is_empty());
+            assert_eq!(
+                a.hull(),
+                a_oracle
+                    .first()
+                    .zip(a_oracle.last())
+                    .map(|(&start, &end)| start..=end)
+            );
+            for value in -25..25 {
+                assert_eq!(a.contains(value), a_oracle.contains(&value));
+            }
+
+            assert_eq!(members(&a.union(&b)), &a_oracle | &b_oracle);
+            assert_eq!(members(&a.intersection(&b)), &a_oracle & &b_oracle);
+            assert_eq!(members(&a.difference(&b)), &a_oracle - &b_oracle);
+            assert_eq!(a.is_superset(&b), a_oracle.is_superset(&b_oracle));
+            assert_eq!(a.overlaps(&b), !a_oracle.is_disjoint(&b_oracle));
+        }
+    }
+
+    #[test]
+    fn covered_at_least_counts_overlaps() {
+        let mut rng = fastrand::Rng::with_seed(2);
+        for _ in 0..500 {
+            let sets = (0..rng.usize(..5))
+                .map(|_| random_set(&mut rng))
+                .collect::<Vec<_>>();
+            for depth in 1..=4 {
+                let expected = (-30..30)
+                    .filter(|value| {
+                        sets.iter()
+                            .filter(|(_, oracle)| oracle.contains(value))
+                            .count()
+                            >= depth
+                    })
+                    .collect::<BTreeSet<_>>();
+                let covered = IntervalSet::covered_at_least(sets.iter().map(|(set, _)| set), depth);
+                assert_eq!(members(&covered), expected);
+            }
+            let max_depth = (-30..30)
+                .map(|value| {
+                    sets.iter()
+                        .filter(|(_, oracle)| oracle.contains(&value))
+                        .count()
+                })
+                .max()
+                .unwrap_or(0);
+            assert_eq!(
+                IntervalSet::max_depth(sets.iter().map(|(set, _)| set)),
+                max_depth
+            );
+            if max_depth > 0 {
+                assert!(
+                    !IntervalSet::covered_at_least(sets.iter().map(|(set, _)| set), max_depth)
+                        .is_empty()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn covered_at_least_zero_is_everything() {
+        let covered = IntervalSet::covered_at_least(&[], 0);
+        assert_eq!(covered.hull(), Some(i64::MIN..=i64::MAX));
+        assert_eq!(covered.ranges().count(), 1);
+        assert_eq!(covered.len(), u64::MAX);
+    }
+
+    #[test]
+    fn extremes_do_not_overflow() {
+        let top = IntervalSet::from(i64::MAX - 2..=i64::MAX);
+        let bottom = IntervalSet::from(i64::MIN..=i64::MIN + 2);
+        assert_eq!(top.len(), 3);
+        assert_eq!(IntervalSet::covered_at_least([&top, &top], 2), top);
+        assert_eq!(IntervalSet::max_depth([&top, &top, &bottom]), 2);
+        assert_eq!(bottom.union(&top).len(), 6);
+        assert_eq!(
+            top.difference(&IntervalSet::from(i64::MAX..=i64::MAX)),
+            IntervalSet::from(i64::MAX - 2..=i64::MAX - 1)
+        );
+        assert_eq!(
+            bottom.union(&top).to_string(),
+            format!(
+                "{}-{},{}-{}",
+                i64::MIN,
+                i64::MIN + 2,
+                i64::MAX - 2,
+                i64::MAX
+            )
+        );
+    }
+}